@@ -0,0 +1,297 @@
+//! Architectures and struct layouts of the syscall ABIs
+//!
+//! Processes of a container can use a compat ABI of the host, like i386 on x86-64 or 32-bit ARM on AArch64.
+//! Their syscall numbers, argument widths and structs differ from the native ones.
+use std::mem::size_of;
+
+use libseccomp::ScmpArch;
+use nix::sys::stat::FileStat;
+
+use crate::mem::Plain;
+
+/// Architectures whose structs have the same layout as the native ones
+#[must_use]
+pub fn native_archs() -> Vec<ScmpArch> {
+    let native = ScmpArch::native();
+    if native == ScmpArch::X8664 {
+        vec![native, ScmpArch::X32]
+    } else {
+        vec![native]
+    }
+}
+
+/// All architectures notifications can be received for: the native ones and the 32-bit compat ABIs
+#[must_use]
+pub fn supported_archs() -> Vec<ScmpArch> {
+    let native = ScmpArch::native();
+    let compat = if native == ScmpArch::X8664 {
+        Some(ScmpArch::X86)
+    } else if native == ScmpArch::Aarch64 {
+        Some(ScmpArch::Arm)
+    } else {
+        None
+    };
+    let mut archs = native_archs();
+    archs.extend(compat);
+    archs
+}
+
+/// Whether pointers and `long` are 32 bits wide in `arch`
+#[must_use]
+pub fn is_32bit(arch: ScmpArch) -> bool {
+    matches!(
+        arch,
+        ScmpArch::X86
+            | ScmpArch::X32
+            | ScmpArch::Arm
+            | ScmpArch::Mips
+            | ScmpArch::Mipsel
+            | ScmpArch::Mips64N32
+            | ScmpArch::Mipsel64N32
+            | ScmpArch::Ppc
+            | ScmpArch::S390
+            | ScmpArch::Parisc
+    )
+}
+
+/// The lower 32 bits of `value`, as the kernel truncates fields that do not fit a compat struct
+fn low32<T: Into<i128>>(value: T) -> u32 {
+    u32::try_from(value.into() & 0xffff_ffff).unwrap_or_default()
+}
+
+/// `struct stat64` of i386, used by `fstatat64`
+///
+/// `long long` is only 4-byte aligned on i386, so the struct has no padding.
+#[allow(missing_docs, clippy::exhaustive_structs)]
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C, packed(4))]
+pub struct Stat64I386 {
+    pub st_dev: u64,
+    pub pad0: [u8; 4],
+    pub st_ino32: u32,
+    pub st_mode: u32,
+    pub st_nlink: u32,
+    pub st_uid: u32,
+    pub st_gid: u32,
+    pub st_rdev: u64,
+    pub pad3: [u8; 4],
+    pub st_size: i64,
+    pub st_blksize: u32,
+    pub st_blocks: u64,
+    pub st_atime: u32,
+    pub st_atime_nsec: u32,
+    pub st_mtime: u32,
+    pub st_mtime_nsec: u32,
+    pub st_ctime: u32,
+    pub st_ctime_nsec: u32,
+    pub st_ino: u64,
+}
+
+impl From<&FileStat> for Stat64I386 {
+    fn from(stat: &FileStat) -> Self {
+        Self {
+            st_dev: stat.st_dev,
+            st_ino32: low32(stat.st_ino),
+            st_mode: stat.st_mode,
+            st_nlink: low32(stat.st_nlink),
+            st_uid: stat.st_uid,
+            st_gid: stat.st_gid,
+            st_rdev: stat.st_rdev,
+            st_size: stat.st_size,
+            st_blksize: low32(stat.st_blksize),
+            st_blocks: u64::try_from(stat.st_blocks).unwrap_or_default(),
+            st_atime: low32(stat.st_atime),
+            st_atime_nsec: low32(stat.st_atime_nsec),
+            st_mtime: low32(stat.st_mtime),
+            st_mtime_nsec: low32(stat.st_mtime_nsec),
+            st_ctime: low32(stat.st_ctime),
+            st_ctime_nsec: low32(stat.st_ctime_nsec),
+            st_ino: stat.st_ino,
+            ..Self::default()
+        }
+    }
+}
+
+const _: () = assert!(
+    size_of::<Stat64I386>() == 96,
+    "i386 struct stat64 is 96 bytes"
+);
+
+#[allow(unsafe_code)]
+/// SAFETY:
+/// `repr(C)` with only integer fields, `packed(4)` matches the i386 alignment and leaves no padding.
+unsafe impl Plain for Stat64I386 {}
+
+/// `struct stat64` of 32-bit ARM (EABI), used by `fstatat64`
+///
+/// `long long` is 8-byte aligned on EABI, the implicit padding is spelled out as `pad4` and `pad5`.
+#[allow(missing_docs, clippy::exhaustive_structs)]
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C)]
+pub struct Stat64Arm {
+    pub st_dev: u64,
+    pub pad0: [u8; 4],
+    pub st_ino32: u32,
+    pub st_mode: u32,
+    pub st_nlink: u32,
+    pub st_uid: u32,
+    pub st_gid: u32,
+    pub st_rdev: u64,
+    pub pad3: [u8; 4],
+    pub pad4: u32,
+    pub st_size: i64,
+    pub st_blksize: u32,
+    pub pad5: u32,
+    pub st_blocks: u64,
+    pub st_atime: u32,
+    pub st_atime_nsec: u32,
+    pub st_mtime: u32,
+    pub st_mtime_nsec: u32,
+    pub st_ctime: u32,
+    pub st_ctime_nsec: u32,
+    pub st_ino: u64,
+}
+
+impl From<&FileStat> for Stat64Arm {
+    fn from(stat: &FileStat) -> Self {
+        Self {
+            st_dev: stat.st_dev,
+            st_ino32: low32(stat.st_ino),
+            st_mode: stat.st_mode,
+            st_nlink: low32(stat.st_nlink),
+            st_uid: stat.st_uid,
+            st_gid: stat.st_gid,
+            st_rdev: stat.st_rdev,
+            st_size: stat.st_size,
+            st_blksize: low32(stat.st_blksize),
+            st_blocks: u64::try_from(stat.st_blocks).unwrap_or_default(),
+            st_atime: low32(stat.st_atime),
+            st_atime_nsec: low32(stat.st_atime_nsec),
+            st_mtime: low32(stat.st_mtime),
+            st_mtime_nsec: low32(stat.st_mtime_nsec),
+            st_ctime: low32(stat.st_ctime),
+            st_ctime_nsec: low32(stat.st_ctime_nsec),
+            st_ino: stat.st_ino,
+            ..Self::default()
+        }
+    }
+}
+
+const _: () = assert!(
+    size_of::<Stat64Arm>() == 104,
+    "ARM struct stat64 is 104 bytes"
+);
+
+#[allow(unsafe_code)]
+/// SAFETY:
+/// `repr(C)` with only integer fields, the padding required by the alignment of `u64` is explicit.
+unsafe impl Plain for Stat64Arm {}
+
+/// `struct __user_cap_header_struct` of `capget(2)` and `capset(2)`, the same in every ABI
+#[allow(missing_docs, clippy::exhaustive_structs)]
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C)]
+pub struct CapUserHeader {
+    pub version: u32,
+    pub pid: i32,
+}
+
+#[allow(unsafe_code)]
+/// SAFETY:
+/// `repr(C)` with two 4-byte integers, which leaves no padding.
+unsafe impl Plain for CapUserHeader {}
+
+/// `struct __user_cap_data_struct` of `capget(2)` and `capset(2)`, one for every 32 capabilities
+#[allow(missing_docs, clippy::exhaustive_structs)]
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C)]
+pub struct CapUserData {
+    pub effective: u32,
+    pub permitted: u32,
+    pub inheritable: u32,
+}
+
+#[allow(unsafe_code)]
+/// SAFETY:
+/// `repr(C)` with three 4-byte integers, which leaves no padding.
+unsafe impl Plain for CapUserData {}
+
+/// `struct msghdr` of 64-bit ABIs, used by `sendmsg(2)` and `recvmsg(2)`
+///
+/// The padding after `msg_namelen` and `msg_flags` required by the alignment of pointers is spelled out as `pad0` and `pad1`.
+#[allow(missing_docs, clippy::exhaustive_structs)]
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C)]
+pub struct Msghdr64 {
+    pub msg_name: u64,
+    pub msg_namelen: u32,
+    pub pad0: u32,
+    pub msg_iov: u64,
+    pub msg_iovlen: u64,
+    pub msg_control: u64,
+    pub msg_controllen: u64,
+    pub msg_flags: i32,
+    pub pad1: u32,
+}
+
+const _: () = assert!(
+    size_of::<Msghdr64>() == 56,
+    "64-bit struct msghdr is 56 bytes"
+);
+
+#[allow(unsafe_code)]
+/// SAFETY:
+/// `repr(C)` with only integer fields, the padding required by the alignment of `u64` is explicit.
+unsafe impl Plain for Msghdr64 {}
+
+/// `struct compat_msghdr` of 32-bit ABIs
+#[allow(missing_docs, clippy::exhaustive_structs)]
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C)]
+pub struct Msghdr32 {
+    pub msg_name: u32,
+    pub msg_namelen: u32,
+    pub msg_iov: u32,
+    pub msg_iovlen: u32,
+    pub msg_control: u32,
+    pub msg_controllen: u32,
+    pub msg_flags: i32,
+}
+
+const _: () = assert!(
+    size_of::<Msghdr32>() == 28,
+    "struct compat_msghdr is 28 bytes"
+);
+
+#[allow(unsafe_code)]
+/// SAFETY:
+/// `repr(C)` with only 4-byte integers, which leaves no padding.
+unsafe impl Plain for Msghdr32 {}
+
+/// `struct iovec` of 64-bit ABIs
+#[allow(missing_docs, clippy::exhaustive_structs)]
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C)]
+pub struct Iovec64 {
+    pub iov_base: u64,
+    pub iov_len: u64,
+}
+
+#[allow(unsafe_code)]
+/// SAFETY:
+/// `repr(C)` with two 8-byte integers, which leaves no padding.
+unsafe impl Plain for Iovec64 {}
+
+/// `struct compat_iovec` of 32-bit ABIs
+#[allow(missing_docs, clippy::exhaustive_structs)]
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C)]
+pub struct Iovec32 {
+    pub iov_base: u32,
+    pub iov_len: u32,
+}
+
+#[allow(unsafe_code)]
+/// SAFETY:
+/// `repr(C)` with two 4-byte integers, which leaves no padding.
+unsafe impl Plain for Iovec32 {}
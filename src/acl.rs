@@ -0,0 +1,209 @@
+//! POSIX ACLs as stored in the `system.posix_acl_access` and `system.posix_acl_default` xAttributes
+//!
+//! The value is a little endian `u32` version followed by entries of a `u16` tag, a `u16` permission and a `u32` id,
+//! see `struct posix_acl_xattr_header` and `struct posix_acl_xattr_entry` of the kernel.
+use std::ops::Range;
+
+use nix::errno::Errno;
+use nix::libc::mode_t;
+
+/// Permissions of the owner
+pub const ACL_USER_OBJ: u16 = 0x01;
+/// Permissions of a user given by the id
+pub const ACL_USER: u16 = 0x02;
+/// Permissions of the owning group
+pub const ACL_GROUP_OBJ: u16 = 0x04;
+/// Permissions of a group given by the id
+pub const ACL_GROUP: u16 = 0x08;
+/// Upper bound of the permissions of the group class
+pub const ACL_MASK: u16 = 0x10;
+/// Permissions of everyone else
+pub const ACL_OTHER: u16 = 0x20;
+
+/// Version of the xAttribute representation
+const POSIX_ACL_XATTR_VERSION: u32 = 2;
+/// Size of the version header
+const HEADER_SIZE: usize = 4;
+/// Size of an entry
+const ENTRY_SIZE: usize = 8;
+
+/// An entry of an ACL
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::exhaustive_structs)]
+pub struct Entry {
+    /// One of the `ACL_*` tags
+    pub tag: u16,
+    /// Read, write and execute permission bits
+    pub perm: u16,
+    /// User or group id of `ACL_USER` and `ACL_GROUP` entries, `u32::MAX` otherwise
+    pub id: u32,
+}
+
+/// A POSIX ACL
+///
+/// # Examples
+/// ```
+/// use subuidless::acl::{Acl, Entry, ACL_GROUP_OBJ, ACL_MASK, ACL_OTHER, ACL_USER, ACL_USER_OBJ};
+///
+/// let entry = |tag, perm, id| Entry { tag, perm, id };
+/// let mut acl = Acl {
+///     entries: vec![
+///         entry(ACL_USER_OBJ, 7, u32::MAX),
+///         entry(ACL_USER, 7, 1000),
+///         entry(ACL_GROUP_OBJ, 5, u32::MAX),
+///         entry(ACL_MASK, 7, u32::MAX),
+///         entry(ACL_OTHER, 0, u32::MAX),
+///     ],
+/// };
+/// assert_eq!(Acl::parse(&acl.to_bytes()), Ok(acl.clone()));
+/// assert!(!acl.is_minimal());
+/// assert_eq!(acl.mode(), 0o770);
+///
+/// acl.set_mode(0o750);
+/// assert_eq!(acl.entries[3].perm, 5);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[allow(clippy::exhaustive_structs)]
+pub struct Acl {
+    /// The entries in the order of their tags
+    pub entries: Vec<Entry>,
+}
+
+impl Acl {
+    /// Parses and validates the xAttribute representation like `posix_acl_valid` of the kernel:
+    /// the owner, owning group and other entries exist exactly once,
+    /// and named entries require a mask.
+    #[allow(clippy::little_endian_bytes)] // The representation is little endian on every architecture
+    pub fn parse(value: &[u8]) -> Result<Self, Errno> {
+        let (header, entries) = value
+            .split_first_chunk::<HEADER_SIZE>()
+            .ok_or(Errno::EINVAL)?;
+        if u32::from_le_bytes(*header) != POSIX_ACL_XATTR_VERSION {
+            return Err(Errno::EOPNOTSUPP);
+        }
+        if entries.len().checked_rem(ENTRY_SIZE) != Some(0) {
+            return Err(Errno::EINVAL);
+        }
+
+        let acl = Self {
+            entries: entries
+                .chunks_exact(ENTRY_SIZE)
+                .map(|entry| {
+                    let field = |range: Range<usize>| entry.get(range).unwrap_or_default();
+                    Entry {
+                        tag: u16::from_le_bytes(field(0..2).try_into().unwrap_or_default()),
+                        perm: u16::from_le_bytes(field(2..4).try_into().unwrap_or_default()),
+                        id: u32::from_le_bytes(field(4..8).try_into().unwrap_or_default()),
+                    }
+                })
+                .collect(),
+        };
+        if acl.is_empty() || acl.is_valid() {
+            Ok(acl)
+        } else {
+            Err(Errno::EINVAL)
+        }
+    }
+
+    /// The xAttribute representation
+    #[must_use]
+    #[allow(clippy::little_endian_bytes)] // The representation is little endian on every architecture
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut value = POSIX_ACL_XATTR_VERSION.to_le_bytes().to_vec();
+        for entry in &self.entries {
+            value.extend_from_slice(&entry.tag.to_le_bytes());
+            value.extend_from_slice(&entry.perm.to_le_bytes());
+            value.extend_from_slice(&entry.id.to_le_bytes());
+        }
+        value
+    }
+
+    /// Whether the ACL has no entries, which removes it
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Whether the ACL only consists of the entries the permission bits of the mode represent
+    #[must_use]
+    pub fn is_minimal(&self) -> bool {
+        self.entries
+            .iter()
+            .all(|entry| matches!(entry.tag, ACL_USER_OBJ | ACL_GROUP_OBJ | ACL_OTHER))
+    }
+
+    /// The permission bits of the mode the ACL implies, the mask takes the place of the owning group
+    #[must_use]
+    pub fn mode(&self) -> mode_t {
+        let owner = self.perm(ACL_USER_OBJ);
+        let group = self.group_class().map_or(0, |entry| entry.perm);
+        let other = self.perm(ACL_OTHER);
+        (mode_t::from(owner & 7) << 6_u32)
+            | (mode_t::from(group & 7) << 3_u32)
+            | mode_t::from(other & 7)
+    }
+
+    /// Updates the entries the permission bits of `mode` represent, as `chmod(2)` does
+    pub fn set_mode(&mut self, mode: mode_t) {
+        let bits = |shift: u32| u16::try_from((mode >> shift) & 7).unwrap_or_default();
+        let has_mask = self.entries.iter().any(|entry| entry.tag == ACL_MASK);
+        for entry in &mut self.entries {
+            match entry.tag {
+                ACL_USER_OBJ => entry.perm = bits(6),
+                ACL_MASK => entry.perm = bits(3),
+                ACL_GROUP_OBJ if !has_mask => entry.perm = bits(3),
+                ACL_OTHER => entry.perm = bits(0),
+                _ => {}
+            }
+        }
+    }
+
+    /// The permissions of the first entry with `tag`
+    fn perm(&self, tag: u16) -> u16 {
+        self.entries
+            .iter()
+            .find(|entry| entry.tag == tag)
+            .map_or(0, |entry| entry.perm)
+    }
+
+    /// The entry of the group class, the mask if there is one or else the owning group
+    fn group_class(&self) -> Option<&Entry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.tag == ACL_MASK)
+            .or_else(|| self.entries.iter().find(|entry| entry.tag == ACL_GROUP_OBJ))
+    }
+
+    /// Whether the entries appear in the order of their tags with the required ones exactly once
+    fn is_valid(&self) -> bool {
+        let needs_mask = self
+            .entries
+            .iter()
+            .any(|entry| matches!(entry.tag, ACL_USER | ACL_GROUP));
+        let mut expected = vec![ACL_USER_OBJ, ACL_GROUP_OBJ];
+        if needs_mask {
+            expected.push(ACL_MASK);
+        }
+        expected.push(ACL_OTHER);
+
+        let mut last = 0;
+        let mut required = Vec::new();
+        for entry in &self.entries {
+            let named = matches!(entry.tag, ACL_USER | ACL_GROUP);
+            if entry.perm & !7 != 0
+                || entry.tag < last
+                || (named && entry.id == u32::MAX)
+                || (!named && entry.tag == last)
+            {
+                return false;
+            }
+            if !named {
+                required.push(entry.tag);
+            }
+            last = entry.tag;
+        }
+        // The mask is optional without named entries
+        required.retain(|&tag| tag != ACL_MASK || needs_mask);
+        required == expected
+    }
+}
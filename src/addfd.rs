@@ -0,0 +1,90 @@
+//! Injection of file descriptors into the process that issued a syscall
+//!
+//! `SECCOMP_IOCTL_NOTIF_ADDFD` installs a file descriptor of the daemon in the caller.
+//! With `SECCOMP_ADDFD_FLAG_SEND` (Linux 5.14) the same ioctl answers the notification with the number of the new descriptor,
+//! so the descriptor and the return value of the syscall appear atomically, like for a syscall executed by the kernel.
+use std::os::fd::{AsFd, AsRawFd, RawFd};
+
+use libseccomp::ScmpFd;
+use nix::errno::Errno;
+use nix::fcntl::OFlag;
+use nix::libc::{seccomp_notif_addfd, SECCOMP_ADDFD_FLAG_SEND};
+
+use crate::error::attach;
+
+#[allow(unsafe_code)]
+mod ioctl {
+    use nix::ioctl_write_ptr;
+    use nix::libc::seccomp_notif_addfd;
+
+    ioctl_write_ptr!(
+        /// `SECCOMP_IOCTL_NOTIF_ADDFD`, see `seccomp_unotify(2)`
+        notif_addfd,
+        b'!',
+        3,
+        seccomp_notif_addfd
+    );
+}
+
+/// Installs `fd` in the process that issued the notification `id` received on `notify_fd` and returns its number there.
+/// `flags` may only contain `O_CLOEXEC`.
+/// The notification still has to be answered, use `send_fd` if the number is the return value of the syscall.
+pub fn add_fd<Fd: AsFd>(
+    notify_fd: ScmpFd,
+    id: u64,
+    fd: Fd,
+    flags: OFlag,
+) -> Result<RawFd, crate::Error> {
+    Ok(notif_addfd(
+        notify_fd,
+        id,
+        fd.as_fd().as_raw_fd(),
+        flags,
+        0,
+    )?)
+}
+
+/// Installs `fd` in the process that issued the notification `id` and answers the notification with its number.
+/// Returns `None` if the notification was answered,
+/// or the number of the descriptor on kernels without `SECCOMP_ADDFD_FLAG_SEND`, which has to be returned by the caller.
+pub fn send_fd<Fd: AsFd>(
+    notify_fd: ScmpFd,
+    id: u64,
+    fd: Fd,
+    flags: OFlag,
+) -> Result<Option<RawFd>, crate::Error> {
+    let raw_fd = fd.as_fd().as_raw_fd();
+    let send = u32::try_from(SECCOMP_ADDFD_FLAG_SEND).map_err(attach(Errno::EINVAL))?;
+    match notif_addfd(notify_fd, id, raw_fd, flags, send) {
+        Ok(_) => Ok(None),
+        // Older kernels reject the unknown flag
+        Err(Errno::EINVAL) => Ok(Some(notif_addfd(notify_fd, id, raw_fd, flags, 0)?)),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Issues `SECCOMP_IOCTL_NOTIF_ADDFD` with the `SECCOMP_ADDFD_FLAG_*` `addfd_flags`
+fn notif_addfd(
+    notify_fd: ScmpFd,
+    id: u64,
+    fd: RawFd,
+    flags: OFlag,
+    addfd_flags: u32,
+) -> Result<RawFd, Errno> {
+    if flags.intersects(!OFlag::O_CLOEXEC) {
+        return Err(Errno::EINVAL);
+    }
+    let addfd = seccomp_notif_addfd {
+        id,
+        flags: addfd_flags,
+        srcfd: u32::try_from(fd).map_err(|_err| Errno::EBADF)?,
+        newfd: 0,
+        newfd_flags: u32::try_from(flags.bits()).map_err(|_err| Errno::EINVAL)?,
+    };
+    #[allow(unsafe_code)]
+    // SAFETY:
+    // `addfd` is a valid `seccomp_notif_addfd` that outlives the call, the kernel only reads it
+    unsafe {
+        ioctl::notif_addfd(notify_fd, &addfd)
+    }
+}
@@ -0,0 +1,77 @@
+//! Authentication of the clients connecting to the socket
+//!
+//! A client hands over a seccomp notify fd together with the pid of the container.
+//! The daemon then writes into the memory of the container, so before acting on it both the client
+//! and its claim on the container are verified.
+use std::os::fd::OwnedFd;
+use std::os::unix::net::UnixStream;
+
+use anyhow::{ensure, Context, Result};
+use nix::libc::uid_t;
+use procfs::process::Process;
+use rustix::event::{poll, PollFd, PollFlags};
+use rustix::fs::stat;
+use rustix::net::sockopt::get_socket_peercred;
+use rustix::net::UCred;
+use rustix::process::{pidfd_open, Pid, PidfdFlags};
+
+/// Returns the credentials of the peer if its uid is in `allowed_uids`
+pub fn authenticate(stream: &UnixStream, allowed_uids: &[uid_t]) -> Result<UCred> {
+    let peer = get_socket_peercred(stream).context("Could not get the peer credentials")?;
+    ensure!(
+        allowed_uids.contains(&peer.uid.as_raw()),
+        "Peer {} with uid {} is not allowed to connect",
+        peer.pid.as_raw_nonzero(),
+        peer.uid.as_raw()
+    );
+    Ok(peer)
+}
+
+/// Opens a pidfd for the container process `pid` after checking that `peer` would be allowed to ptrace it.
+/// Follows the rules of `ptrace(2)`: Unless the peer is root, the real, effective and saved ids of the target
+/// must match the ids of the peer and the target must be dumpable.
+///
+/// The pidfd is opened *before* the checks and checked for liveness *after* them,
+/// so the checks can not be fooled by a recycled pid.
+pub fn open_target(peer: &UCred, pid: Pid) -> Result<OwnedFd> {
+    let pid_fd =
+        pidfd_open(pid, PidfdFlags::empty()).context("Could not open the container process")?;
+
+    if !peer.uid.is_root() {
+        let raw_pid = pid.as_raw_nonzero().get();
+        let status = Process::new(raw_pid)
+            .and_then(|process| process.status())
+            .context("Could not read the status of the container process")?;
+        let uid = peer.uid.as_raw();
+        let gid = peer.gid.as_raw();
+        ensure!(
+            [status.ruid, status.euid, status.suid]
+                .iter()
+                .all(|&id| id == uid)
+                && [status.rgid, status.egid, status.sgid]
+                    .iter()
+                    .all(|&id| id == gid),
+            "Peer with uid {uid} may not trace process {raw_pid}"
+        );
+
+        // `/proc/<pid>` is owned by root if the process is not dumpable
+        let owner = stat(format!("/proc/{raw_pid}"))
+            .context("Could not stat the container process")?
+            .st_uid;
+        ensure!(owner == uid, "Process {raw_pid} is not dumpable");
+    }
+
+    ensure_alive(&pid_fd, pid)?;
+    Ok(pid_fd)
+}
+
+/// Fails if the container process `pid` of `pid_fd` exited, so its pid might refer to another process by now
+pub fn ensure_alive(pid_fd: &OwnedFd, pid: Pid) -> Result<()> {
+    let mut poll_fds = [PollFd::new(pid_fd, PollFlags::IN)];
+    ensure!(
+        poll(&mut poll_fds, 0)? == 0,
+        "Container process {} exited",
+        pid.as_raw_nonzero()
+    );
+    Ok(())
+}
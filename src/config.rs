@@ -0,0 +1,103 @@
+//! Configuration of the `subuidless` daemon
+//!
+//! Values are read from a JSON file and can be overridden on the command line.
+use std::fs::File;
+use std::io::BufReader;
+use std::num::{NonZeroUsize, ParseIntError};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use log::LevelFilter;
+use nix::libc::uid_t;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+
+/// Daemon configuration as read from the config file
+///
+/// # Examples
+/// ```
+/// use subuidless::config::{Config, OutsidePolicy};
+///
+/// let config: Config = serde_json::from_str(r#"{ "socket_mode": "0660", "handlers": ["fchownat"] }"#).unwrap();
+/// assert_eq!(config.socket_mode, Some(0o660));
+/// assert!(config.is_enabled("fchownat"));
+/// assert!(!config.is_enabled("newfstatat"));
+/// assert_eq!(config.outside, OutsidePolicy::Continue);
+/// assert!(!config.permission_checks);
+/// ```
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
+pub struct Config {
+    /// Path of the listening socket, defaults to `$XDG_RUNTIME_DIR/subuidless.sock`
+    pub socket: Option<PathBuf>,
+    /// Permissions of the socket file, given as an octal string
+    #[serde(deserialize_with = "deserialize_mode")]
+    pub socket_mode: Option<u32>,
+    /// Log verbosity (`off`, `error`, `warn`, `info`, `debug`, `trace`)
+    pub log_level: Option<LevelFilter>,
+    /// Names of the syscalls to emulate. All syscalls are emulated if unset
+    pub handlers: Option<Vec<String>>,
+    /// Uids allowed to connect to the socket, defaults to the uid of the daemon
+    pub allowed_uids: Option<Vec<uid_t>>,
+    /// Number of threads handling the notifications of each container, defaults to the number of CPUs
+    pub threads: Option<NonZeroUsize>,
+    /// How to answer syscalls of processes outside the PID namespace of the container
+    pub outside: OutsidePolicy,
+    /// Emulate permission checks like `access(2)` against the emulated ownership of files,
+    /// and the privilege rules of `chown(2)` and `chmod(2)`.
+    /// Off by default, subuidless is no security boundary, and the kernel answers these syscalls instead.
+    pub permission_checks: bool,
+}
+
+/// Policy for notifications of processes that are not part of the container,
+/// e.g. the runtime while it sets the container up
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[allow(clippy::exhaustive_enums)] // Every policy needs to be handled by the worker
+pub enum OutsidePolicy {
+    /// Let the kernel execute the syscall without emulation
+    #[default]
+    Continue,
+    /// Fail the syscall with `EPERM`
+    Deny,
+}
+
+impl Config {
+    /// Read the configuration from a JSON file
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .with_context(|| format!("Could not open config file {}", path.display()))?;
+        serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("Could not parse config file {}", path.display()))
+    }
+
+    /// Whether the handler for the syscall `name` should be registered
+    #[must_use]
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.handlers.as_ref().map_or(true, |handlers| {
+            handlers.iter().any(|handler| handler == name)
+        })
+    }
+}
+
+/// Parse a file mode given in octal notation, with or without a leading `0o`
+///
+/// # Examples
+/// ```
+/// use subuidless::config::parse_mode;
+///
+/// assert_eq!(parse_mode("0600"), Ok(0o600));
+/// assert_eq!(parse_mode("0o660"), Ok(0o660));
+/// assert!(parse_mode("rw").is_err());
+/// ```
+pub fn parse_mode(mode: &str) -> Result<u32, ParseIntError> {
+    u32::from_str_radix(mode.trim_start_matches("0o"), 8)
+}
+
+fn deserialize_mode<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|mode| parse_mode(&mode).map_err(Error::custom))
+        .transpose()
+}
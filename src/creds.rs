@@ -0,0 +1,150 @@
+//! Credentials of the process that issued a syscall and the permission checks based on them
+//!
+//! The ids are the emulated ones of `identity`, translated into the user namespace of the process,
+//! so they can be compared with the emulated ownership of files.
+use nix::libc::{gid_t, mode_t, uid_t, S_IFDIR, S_IFMT};
+use nix::unistd::AccessFlags;
+
+use crate::identity::Identity;
+
+/// Change the owner and group of any file
+pub const CAP_CHOWN: u32 = 0;
+/// Bypass read, write and execute permission checks
+pub const CAP_DAC_OVERRIDE: u32 = 1;
+/// Bypass read permission checks and search permission checks of directories
+pub const CAP_DAC_READ_SEARCH: u32 = 2;
+/// Bypass the checks that require the caller to own the file, e.g. for `chmod(2)`
+pub const CAP_FOWNER: u32 = 3;
+/// Keep the set-group-ID bit of files whose group the caller is not in
+pub const CAP_FSETID: u32 = 4;
+/// Change the group ids arbitrarily
+pub const CAP_SETGID: u32 = 6;
+/// Change the user ids arbitrarily
+pub const CAP_SETUID: u32 = 7;
+/// Add any capability to the inheritable set
+pub const CAP_SETPCAP: u32 = 8;
+/// Bypass the permission checks of System V IPC objects
+pub const CAP_IPC_OWNER: u32 = 15;
+/// Perform administrative operations, e.g. changing System V IPC objects of other users
+pub const CAP_SYS_ADMIN: u32 = 21;
+/// Set the capabilities of files
+pub const CAP_SETFCAP: u32 = 31;
+
+/// Securebit that turns off the capabilities uid 0 gains on `execve(2)`
+pub const SECBIT_NOROOT: u32 = 0x01;
+/// Securebit that turns off the capability adjustments of user id changes
+pub const SECBIT_NO_SETUID_FIXUP: u32 = 0x04;
+/// Securebit that keeps the permitted capabilities once all user ids become non-zero, cleared by `execve(2)`
+pub const SECBIT_KEEP_CAPS: u32 = 0x10;
+/// Lock of `SECBIT_KEEP_CAPS`
+pub const SECBIT_KEEP_CAPS_LOCKED: u32 = 0x20;
+/// All securebits that can be set, including `SECBIT_NO_CAP_AMBIENT_RAISE`
+pub const SECBIT_ALL: u32 = 0x55;
+/// The locks of all securebits, each one the bit above the one it locks
+pub const SECBIT_ALL_LOCKS: u32 = 0xaa;
+
+/// The ids a process is checked with, inside of its user namespace
+///
+/// # Examples
+/// ```
+/// use nix::unistd::AccessFlags;
+/// use subuidless::creds::Credentials;
+///
+/// let user = Credentials { uid: 1000, gid: 1000, groups: vec![], caps: 0 };
+/// assert!(user.may_access(1000, 0, 0o100600, AccessFlags::R_OK | AccessFlags::W_OK));
+/// assert!(!user.may_access(0, 0, 0o100644, AccessFlags::W_OK));
+///
+/// let root = Credentials { uid: 0, gid: 0, groups: vec![], caps: u64::MAX };
+/// assert!(root.may_access(1000, 1000, 0o100600, AccessFlags::W_OK));
+/// assert!(!root.may_access(1000, 1000, 0o100600, AccessFlags::X_OK));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::exhaustive_structs)]
+pub struct Credentials {
+    /// User id checked against the owner
+    pub uid: uid_t,
+    /// Group id checked against the group
+    pub gid: gid_t,
+    /// Supplementary groups
+    pub groups: Vec<gid_t>,
+    /// Effective capabilities, a bit for each of the `CAP_*` numbers
+    pub caps: u64,
+}
+
+impl Credentials {
+    /// Reads the credentials process `pid` is checked with:
+    /// the filesystem ids if `effective` is set, otherwise the real ids as used by `access(2)`
+    pub fn read(pid: u32, effective: bool) -> Result<Self, crate::Error> {
+        let identity = Identity::get(pid)?;
+        if effective {
+            return Ok(Self {
+                uid: identity.uid.fs,
+                gid: identity.gid.fs,
+                groups: identity.groups,
+                caps: identity.caps.effective,
+            });
+        }
+        // `access(2)` grants the permitted capabilities to a real root user and none to the others
+        let caps = if identity.uid.real == 0 {
+            identity.caps.permitted
+        } else {
+            0
+        };
+        Ok(Self {
+            uid: identity.uid.real,
+            gid: identity.gid.real,
+            groups: identity.groups,
+            caps,
+        })
+    }
+
+    /// Whether the process has the capability `cap` in its user namespace
+    #[must_use]
+    pub fn has_capability(&self, cap: u32) -> bool {
+        self.caps.checked_shr(cap).is_some_and(|caps| caps & 1 == 1)
+    }
+
+    /// Whether the process owns a file owned by `uid` or may act as its owner with `CAP_FOWNER`
+    #[must_use]
+    pub fn is_owner(&self, uid: uid_t) -> bool {
+        self.uid == uid || self.has_capability(CAP_FOWNER)
+    }
+
+    /// Whether the process is in the group `gid`
+    #[must_use]
+    pub fn in_group(&self, gid: gid_t) -> bool {
+        self.gid == gid || self.groups.contains(&gid)
+    }
+
+    /// Whether the permission bits of `mode` grant `access` to a file owned by `uid` and `gid`.
+    /// With `CAP_DAC_OVERRIDE` everything may be read and written, and executed if it is executable by anyone or a directory.
+    /// `CAP_DAC_READ_SEARCH` allows reading everything and searching directories.
+    #[must_use]
+    pub fn may_access(&self, uid: uid_t, gid: gid_t, mode: mode_t, access: AccessFlags) -> bool {
+        let requested = access.bits() & 0o7_i32;
+        let directory = mode & S_IFMT == S_IFDIR;
+        let mut bypassed = 0_i32;
+        if self.has_capability(CAP_DAC_READ_SEARCH) {
+            bypassed |= AccessFlags::R_OK.bits();
+            if directory {
+                bypassed |= AccessFlags::X_OK.bits();
+            }
+        }
+        if self.has_capability(CAP_DAC_OVERRIDE) {
+            bypassed |= (AccessFlags::R_OK | AccessFlags::W_OK).bits();
+            if directory || mode & 0o111 != 0 {
+                bypassed |= AccessFlags::X_OK.bits();
+            }
+        }
+
+        let granted = if self.uid == uid {
+            mode >> 6_u32
+        } else if self.in_group(gid) {
+            mode >> 3_u32
+        } else {
+            mode
+        };
+        let granted = i32::try_from(granted & 0o7).unwrap_or_default() | bypassed;
+        granted & requested == requested
+    }
+}
@@ -0,0 +1,691 @@
+//! Emulated credentials of the processes of a container
+//!
+//! Processes start out with the credentials the kernel reports in `/proc/<pid>/status`.
+//! Once a process changes them with an emulated `set*id(2)` or `capset(2)`, the daemon tracks them here,
+//! keyed by the thread group and its start time so a reused pid does not inherit them.
+//! Children take over the credentials of their closest tracked ancestor, as they would on `fork(2)`,
+//! and keep them once their parent changes its credentials or exits.
+//! Only the changes of a process copy its credentials to its descendants, otherwise they are looked up lazily by their ancestry.
+//! A child that is reparented before its first emulated syscall, because its tracked parent exited,
+//! therefore takes over the credentials of its new ancestors instead.
+//! Like glibc does for `set*id(2)`, all threads of a process share the same credentials.
+//! The credentials an emulated `execve(2)` computes are kept aside until `/proc/<pid>/exe` refers to the executable
+//! and the process runs a new image, so a failed `execve(2)` leaves the credentials unchanged.
+use std::collections::BTreeMap;
+use std::fs::{metadata, read_dir, read_to_string, File};
+use std::os::unix::fs::MetadataExt;
+use std::sync::{Mutex, PoisonError};
+
+use anyhow::Context;
+use nix::errno::Errno;
+use nix::libc::{gid_t, uid_t};
+use procfs::process::Stat;
+use procfs::FromRead;
+
+use crate::creds::{
+    CAP_SETGID, CAP_SETPCAP, CAP_SETUID, SECBIT_ALL, SECBIT_ALL_LOCKS, SECBIT_KEEP_CAPS,
+    SECBIT_KEEP_CAPS_LOCKED, SECBIT_NOROOT, SECBIT_NO_SETUID_FIXUP,
+};
+use crate::error::attach;
+use crate::idmap::{IdMap, OVERFLOW_ID};
+use crate::pidns::status;
+
+/// Capabilities that follow the filesystem uid like `CAP_FS_MASK` of the kernel:
+/// `CAP_CHOWN` to `CAP_FSETID`, `CAP_LINUX_IMMUTABLE`, `CAP_MKNOD` and `CAP_MAC_OVERRIDE`
+const FS_CAPS: u64 = 0x1_0800_021f;
+
+/// Tracked credentials by thread group id, together with the start time of the process
+static TRACKED: Mutex<BTreeMap<i32, (u64, Identity)>> = Mutex::new(BTreeMap::new());
+
+/// Credentials of processes in an `execve(2)` by thread group id
+static PENDING: Mutex<BTreeMap<i32, Pending>> = Mutex::new(BTreeMap::new());
+
+/// The device and inode of the executable a process runs, together with the address of its arguments.
+/// Every `execve(2)` places the arguments anew, so the image changes even if a process runs its own executable again.
+type Image = ((u64, u64), u64);
+
+/// Credentials a process takes on once it runs an executable
+#[derive(Debug)]
+struct Pending {
+    /// Start time of the process
+    start_time: u64,
+    /// Device and inode of the executable
+    executable: (u64, u64),
+    /// The image the process ran before the `execve(2)`
+    previous: Image,
+    /// Credentials after the `execve(2)`
+    identity: Identity,
+}
+
+/// The real, effective, saved and filesystem variant of a user or group id
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::exhaustive_structs)]
+pub struct Ids {
+    /// Real id
+    pub real: u32,
+    /// Effective id
+    pub effective: u32,
+    /// Saved set-id
+    pub saved: u32,
+    /// Filesystem id
+    pub fs: u32,
+}
+
+impl Ids {
+    /// Whether `id` is the real, effective or saved id
+    #[must_use]
+    pub fn contains(&self, id: u32) -> bool {
+        self.real == id || self.effective == id || self.saved == id
+    }
+
+    /// Whether the real, effective and saved ids are all non-zero
+    fn all_nonzero(&self) -> bool {
+        !self.contains(0)
+    }
+}
+
+/// The capability sets of a process, a bit for each of the `CAP_*` numbers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::exhaustive_structs)]
+pub struct Capabilities {
+    /// Capabilities used for permission checks
+    pub effective: u64,
+    /// Capabilities the process may make effective
+    pub permitted: u64,
+    /// Capabilities preserved across `execve(2)`
+    pub inheritable: u64,
+}
+
+/// File capabilities of an executable, see `struct vfs_cap_data` of the kernel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::exhaustive_structs)]
+pub struct FileCapabilities {
+    /// Capabilities permitted regardless of the inheritable set of the process
+    pub permitted: u64,
+    /// Capabilities permitted if they are in the inheritable set of the process
+    pub inheritable: u64,
+    /// Whether the permitted capabilities become effective
+    pub effective: bool,
+}
+
+/// What an executable grants on `execve(2)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::exhaustive_structs)]
+pub struct Executable {
+    /// The owner of a set-user-ID executable
+    pub setuid: Option<uid_t>,
+    /// The group of a set-group-ID executable
+    pub setgid: Option<gid_t>,
+    /// The file capabilities
+    pub caps: Option<FileCapabilities>,
+}
+
+/// Credentials of a process inside of its user namespace
+///
+/// # Examples
+/// ```
+/// use subuidless::identity::{Capabilities, Executable, Identity, Ids};
+///
+/// let root = Ids { real: 0, effective: 0, saved: 0, fs: 0 };
+/// let caps = Capabilities { effective: u64::MAX, permitted: u64::MAX, inheritable: 0 };
+/// let mut identity = Identity { uid: root, gid: root, groups: vec![], caps, securebits: 0 };
+///
+/// identity.setresuid(None, Some(1000), None).unwrap();
+/// assert_eq!(identity.caps.effective, 0);
+/// identity.setresuid(None, Some(0), None).unwrap();
+/// assert_eq!(identity.caps.effective, u64::MAX);
+///
+/// let mut kept = identity.clone();
+/// kept.set_keepcaps(true).unwrap();
+/// kept.setresuid(Some(1000), Some(1000), Some(1000)).unwrap();
+/// assert_eq!((kept.caps.permitted, kept.caps.effective), (u64::MAX, 0));
+///
+/// identity.setuid(1000).unwrap();
+/// assert_eq!(identity.caps.permitted, 0);
+/// assert!(identity.setuid(0).is_err());
+///
+/// let setuid_root = Executable { setuid: Some(0), setgid: None, caps: None };
+/// identity.execve(&setuid_root, u64::MAX);
+/// assert_eq!((identity.uid.real, identity.uid.effective, identity.uid.saved), (1000, 0, 0));
+/// assert_eq!(identity.caps.effective, u64::MAX);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::exhaustive_structs)]
+pub struct Identity {
+    /// User ids
+    pub uid: Ids,
+    /// Group ids
+    pub gid: Ids,
+    /// Supplementary groups
+    pub groups: Vec<gid_t>,
+    /// Capability sets
+    pub caps: Capabilities,
+    /// The `SECBIT_*` flags of `PR_SET_SECUREBITS`
+    pub securebits: u32,
+}
+
+impl Identity {
+    /// The credentials of process `pid`: the tracked ones or else the ones the kernel reports
+    pub fn get(pid: u32) -> Result<Self, crate::Error> {
+        match Self::tracked(pid)? {
+            Some(identity) => Ok(identity),
+            None => Self::read(pid),
+        }
+    }
+
+    /// The tracked credentials of process `pid` or of its closest tracked ancestor,
+    /// `None` if the kernel reports the credentials of the process
+    pub fn tracked(pid: u32) -> Result<Option<Self>, crate::Error> {
+        let mut tracked = TRACKED.lock().unwrap_or_else(PoisonError::into_inner);
+        promote(&mut tracked);
+        if tracked.is_empty() {
+            return Ok(None);
+        }
+        find(&mut tracked, pid)
+    }
+
+    /// Reads the credentials the kernel reports for process `pid`, translated into its user namespace
+    pub fn read(pid: u32) -> Result<Self, crate::Error> {
+        let status = status(pid)?;
+        let uid_map = IdMap::read(format!("/proc/{pid}/uid_map")).map_err(attach(Errno::ESRCH))?;
+        let gid_map = IdMap::read(format!("/proc/{pid}/gid_map")).map_err(attach(Errno::ESRCH))?;
+
+        let to_uid = |id| uid_map.to_inside(id).unwrap_or(OVERFLOW_ID);
+        let to_gid = |id| gid_map.to_inside(id).unwrap_or(OVERFLOW_ID);
+        Ok(Self {
+            uid: Ids {
+                real: to_uid(status.ruid),
+                effective: to_uid(status.euid),
+                saved: to_uid(status.suid),
+                fs: to_uid(status.fuid),
+            },
+            gid: Ids {
+                real: to_gid(status.rgid),
+                effective: to_gid(status.egid),
+                saved: to_gid(status.sgid),
+                fs: to_gid(status.fgid),
+            },
+            groups: status
+                .groups
+                .into_iter()
+                .filter_map(|group| gid_t::try_from(group).ok())
+                .map(to_gid)
+                .collect(),
+            caps: Capabilities {
+                effective: status.capeff,
+                permitted: status.capprm,
+                inheritable: status.capinh,
+            },
+            // The kernel does not report the securebits, they are rarely set before the first emulated change
+            securebits: 0,
+        })
+    }
+
+    /// Tracks the credentials as the ones of process `pid` and its future children.
+    /// Existing children keep the credentials they inherited, processes that exited are forgotten.
+    pub fn save(self, pid: u32) -> Result<(), crate::Error> {
+        Self::update(pid, |identity| {
+            *identity = self;
+            Ok(())
+        })
+    }
+
+    /// Applies `change` to the credentials of process `pid` and tracks the result like `save`.
+    /// The credentials are read, changed and tracked under one lock, so concurrent changes of the same process are not lost.
+    /// The credentials are left unchanged if `change` fails.
+    pub fn update<T, F: FnOnce(&mut Self) -> Result<T, crate::Error>>(
+        pid: u32,
+        change: F,
+    ) -> Result<T, crate::Error> {
+        let (tgid, start_time) = process(pid)?;
+        let mut tracked = TRACKED.lock().unwrap_or_else(PoisonError::into_inner);
+        promote(&mut tracked);
+        let current = match find(&mut tracked, pid)? {
+            Some(identity) => identity,
+            None => Self::read(pid)?,
+        };
+        let mut identity = current.clone();
+        let val = change(&mut identity)?;
+        tracked.insert(tgid, (start_time, current));
+        inherit(&mut tracked, tgid);
+        tracked.retain(|&other, &mut (start, _)| {
+            u32::try_from(other)
+                .ok()
+                .and_then(|other| process(other).ok())
+                == Some((other, start))
+        });
+        tracked.insert(tgid, (start_time, identity));
+        Ok(val)
+    }
+
+    /// Tracks the credentials as the ones of process `pid` once it runs the executable with the device and inode `executable`,
+    /// which it only does if its `execve(2)` succeeds
+    pub fn save_on_exec(self, pid: u32, executable: (u64, u64)) -> Result<(), crate::Error> {
+        let (tgid, _) = process(pid)?;
+        // The thread that succeeds takes over the start time of the thread group leader
+        let leader = u32::try_from(tgid).map_err(attach(Errno::ESRCH))?;
+        let (_, start_time) = process(leader)?;
+        let pending = Pending {
+            start_time,
+            executable,
+            previous: image(pid)?,
+            identity: self,
+        };
+        PENDING
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(tgid, pending);
+        Ok(())
+    }
+
+    /// `execve(2)` of `executable`: the effective and saved ids change to the ones of a set-id executable,
+    /// and the capabilities are recomputed with the bounding set `bounding`,
+    /// see "Transformation of capabilities during `execve()`" in `capabilities(7)`.
+    /// Ambient capabilities are not emulated, `SECBIT_KEEP_CAPS` is cleared.
+    pub fn execve(&mut self, executable: &Executable, bounding: u64) {
+        if let Some(uid) = executable.setuid {
+            self.uid.effective = uid;
+        }
+        if let Some(gid) = executable.setgid {
+            self.gid.effective = gid;
+        }
+        self.uid.saved = self.uid.effective;
+        self.uid.fs = self.uid.effective;
+        self.gid.saved = self.gid.effective;
+        self.gid.fs = self.gid.effective;
+
+        let old = self.caps;
+        let (mut permitted, mut effective) = executable.caps.map_or((0, false), |caps| {
+            (
+                (caps.permitted & bounding) | (caps.inheritable & old.inheritable),
+                caps.effective,
+            )
+        });
+        // Root gets all capabilities of the bounding set, unless a set-user-ID root executable
+        // with file capabilities is run by another user
+        let setuid_root_caps = executable.caps.is_some() && executable.setuid == Some(0);
+        let root = self.uid.real == 0 || (self.uid.effective == 0 && !setuid_root_caps);
+        if root && self.securebits & SECBIT_NOROOT == 0 {
+            permitted = bounding | old.inheritable;
+            effective |= self.uid.effective == 0;
+        }
+        self.caps = Capabilities {
+            effective: if effective { permitted } else { 0 },
+            permitted,
+            inheritable: old.inheritable,
+        };
+        self.securebits &= !SECBIT_KEEP_CAPS;
+    }
+
+    /// Whether the process has the capability `cap` in its effective set
+    #[must_use]
+    pub fn has_capability(&self, cap: u32) -> bool {
+        self.caps.effective & bit(cap) != 0
+    }
+
+    /// `setuid(2)`: sets all user ids with `CAP_SETUID`, otherwise the effective one to the real or saved one
+    pub fn setuid(&mut self, uid: uid_t) -> Result<(), Errno> {
+        let old = self.uid;
+        if uid == u32::MAX {
+            return Err(Errno::EINVAL);
+        }
+        if self.has_capability(CAP_SETUID) {
+            self.uid = Ids {
+                real: uid,
+                effective: uid,
+                saved: uid,
+                fs: uid,
+            };
+        } else if uid == old.real || uid == old.saved {
+            self.uid.effective = uid;
+            self.uid.fs = uid;
+        } else {
+            return Err(Errno::EPERM);
+        }
+        self.fix_capabilities(old);
+        Ok(())
+    }
+
+    /// `setreuid(2)`, `None` keeps an id
+    pub fn setreuid(&mut self, real: Option<uid_t>, effective: Option<uid_t>) -> Result<(), Errno> {
+        let old = self.uid;
+        self.uid = set_re(old, real, effective, self.has_capability(CAP_SETUID))?;
+        self.fix_capabilities(old);
+        Ok(())
+    }
+
+    /// `setresuid(2)`, `None` keeps an id
+    pub fn setresuid(
+        &mut self,
+        real: Option<uid_t>,
+        effective: Option<uid_t>,
+        saved: Option<uid_t>,
+    ) -> Result<(), Errno> {
+        let old = self.uid;
+        self.uid = set_res(old, real, effective, saved, self.has_capability(CAP_SETUID))?;
+        self.fix_capabilities(old);
+        Ok(())
+    }
+
+    /// `setfsuid(2)`, returns the previous filesystem uid whether it changed or not
+    pub fn setfsuid(&mut self, fsuid: uid_t) -> uid_t {
+        let old = self.uid.fs;
+        if fsuid != u32::MAX
+            && (self.uid.contains(fsuid) || old == fsuid || self.has_capability(CAP_SETUID))
+        {
+            self.uid.fs = fsuid;
+            let fixup = self.securebits & SECBIT_NO_SETUID_FIXUP == 0;
+            if fixup && old == 0 && fsuid != 0 {
+                self.caps.effective &= !FS_CAPS;
+            }
+            if fixup && old != 0 && fsuid == 0 {
+                self.caps.effective |= self.caps.permitted & FS_CAPS;
+            }
+        }
+        old
+    }
+
+    /// `setgid(2)`: sets all group ids with `CAP_SETGID`, otherwise the effective one to the real or saved one
+    pub fn setgid(&mut self, gid: gid_t) -> Result<(), Errno> {
+        if gid == u32::MAX {
+            return Err(Errno::EINVAL);
+        }
+        if self.has_capability(CAP_SETGID) {
+            self.gid = Ids {
+                real: gid,
+                effective: gid,
+                saved: gid,
+                fs: gid,
+            };
+        } else if gid == self.gid.real || gid == self.gid.saved {
+            self.gid.effective = gid;
+            self.gid.fs = gid;
+        } else {
+            return Err(Errno::EPERM);
+        }
+        Ok(())
+    }
+
+    /// `setregid(2)`, `None` keeps an id
+    pub fn setregid(&mut self, real: Option<gid_t>, effective: Option<gid_t>) -> Result<(), Errno> {
+        self.gid = set_re(self.gid, real, effective, self.has_capability(CAP_SETGID))?;
+        Ok(())
+    }
+
+    /// `setresgid(2)`, `None` keeps an id
+    pub fn setresgid(
+        &mut self,
+        real: Option<gid_t>,
+        effective: Option<gid_t>,
+        saved: Option<gid_t>,
+    ) -> Result<(), Errno> {
+        self.gid = set_res(
+            self.gid,
+            real,
+            effective,
+            saved,
+            self.has_capability(CAP_SETGID),
+        )?;
+        Ok(())
+    }
+
+    /// `setfsgid(2)`, returns the previous filesystem gid whether it changed or not
+    pub fn setfsgid(&mut self, fsgid: gid_t) -> gid_t {
+        let old = self.gid.fs;
+        if fsgid != u32::MAX
+            && (self.gid.contains(fsgid) || old == fsgid || self.has_capability(CAP_SETGID))
+        {
+            self.gid.fs = fsgid;
+        }
+        old
+    }
+
+    /// `capset(2)`: the permitted set may only shrink, the effective one has to be part of it,
+    /// and only permitted capabilities may be added to the inheritable set without `CAP_SETPCAP`
+    pub fn capset(&mut self, caps: Capabilities) -> Result<(), Errno> {
+        let old = self.caps;
+        let inheritable = if self.has_capability(CAP_SETPCAP) {
+            u64::MAX
+        } else {
+            old.inheritable | old.permitted
+        };
+        if caps.inheritable & !inheritable != 0
+            || caps.permitted & !old.permitted != 0
+            || caps.effective & !caps.permitted != 0
+        {
+            return Err(Errno::EPERM);
+        }
+        self.caps = caps;
+        Ok(())
+    }
+
+    /// `prctl(PR_SET_KEEPCAPS)`, which fails if `SECBIT_KEEP_CAPS` is locked
+    pub fn set_keepcaps(&mut self, keep: bool) -> Result<(), Errno> {
+        if self.securebits & SECBIT_KEEP_CAPS_LOCKED != 0 {
+            return Err(Errno::EPERM);
+        }
+        if keep {
+            self.securebits |= SECBIT_KEEP_CAPS;
+        } else {
+            self.securebits &= !SECBIT_KEEP_CAPS;
+        }
+        Ok(())
+    }
+
+    /// `prctl(PR_SET_SECUREBITS)`: requires `CAP_SETPCAP`, locked bits may not change and locks may not be released
+    pub fn set_securebits(&mut self, securebits: u64) -> Result<(), Errno> {
+        let old = u64::from(self.securebits);
+        let locks = old & u64::from(SECBIT_ALL_LOCKS);
+        if locks.checked_shr(1).unwrap_or_default() & (old ^ securebits) != 0
+            || locks & !securebits != 0
+            || securebits & !u64::from(SECBIT_ALL | SECBIT_ALL_LOCKS) != 0
+            || !self.has_capability(CAP_SETPCAP)
+        {
+            return Err(Errno::EPERM);
+        }
+        self.securebits = u32::try_from(securebits).map_err(|_err| Errno::EPERM)?;
+        Ok(())
+    }
+
+    /// Adjusts the capabilities after the user ids changed from `old`, see "Effect of user ID changes on capabilities" in `capabilities(7)`.
+    /// `SECBIT_KEEP_CAPS` keeps the permitted capabilities, `SECBIT_NO_SETUID_FIXUP` turns the adjustments off.
+    fn fix_capabilities(&mut self, old: Ids) {
+        if self.securebits & SECBIT_NO_SETUID_FIXUP != 0 {
+            return;
+        }
+        let new = self.uid;
+        if !old.all_nonzero() && new.all_nonzero() && self.securebits & SECBIT_KEEP_CAPS == 0 {
+            self.caps.permitted = 0;
+            self.caps.effective = 0;
+        }
+        if old.effective == 0 && new.effective != 0 {
+            self.caps.effective = 0;
+        }
+        if old.effective != 0 && new.effective == 0 {
+            self.caps.effective = self.caps.permitted;
+        }
+    }
+}
+
+/// The tracked credentials of process `pid` or of its closest tracked ancestor, which the process takes over
+fn find(
+    tracked: &mut BTreeMap<i32, (u64, Identity)>,
+    pid: u32,
+) -> Result<Option<Identity>, crate::Error> {
+    let (tgid, start_time) = process(pid)?;
+    let mut ancestor = pid;
+    // An ancestor that is not alive anymore ends the search, its children were reparented
+    while let Ok((ancestor_tgid, ancestor_start)) = process(ancestor) {
+        let found = tracked
+            .get(&ancestor_tgid)
+            .filter(|entry| entry.0 == ancestor_start)
+            .map(|entry| entry.1.clone());
+        if let Some(identity) = found {
+            if ancestor_tgid != tgid {
+                tracked.insert(tgid, (start_time, identity.clone()));
+            }
+            return Ok(Some(identity));
+        }
+        match parent(ancestor) {
+            Some(parent) => ancestor = parent,
+            None => break,
+        }
+    }
+    Ok(None)
+}
+
+/// Tracks the credentials of the descendants of the tracked process `tgid`, which they inherited on `fork(2)`.
+/// Otherwise they would take over later changes of their parent, and lose the credentials once it exits and they are reparented.
+fn inherit(tracked: &mut BTreeMap<i32, (u64, Identity)>, tgid: i32) {
+    let mut parents = vec![tgid];
+    while let Some(parent) = parents.pop() {
+        let Some((start_time, identity)) = tracked.get(&parent).cloned() else {
+            continue;
+        };
+        let Ok(pid) = u32::try_from(parent) else {
+            continue;
+        };
+        // The pid of an exited process might be reused by now
+        if process(pid).ok() != Some((parent, start_time)) {
+            continue;
+        }
+        for child in children(pid) {
+            let Ok((child_tgid, child_start)) = process(child) else {
+                continue;
+            };
+            if tracked
+                .get(&child_tgid)
+                .is_some_and(|entry| entry.0 == child_start)
+            {
+                continue;
+            }
+            tracked.insert(child_tgid, (child_start, identity.clone()));
+            parents.push(child_tgid);
+        }
+    }
+}
+
+/// Tracks the pending credentials of processes that run a new image of their executable by now,
+/// and forgets the ones of processes that exited
+fn promote(tracked: &mut BTreeMap<i32, (u64, Identity)>) {
+    let mut pending = PENDING.lock().unwrap_or_else(PoisonError::into_inner);
+    pending.retain(|&tgid, entry| {
+        let Ok(pid) = u32::try_from(tgid) else {
+            return false;
+        };
+        if process(pid).ok() != Some((tgid, entry.start_time)) {
+            return false;
+        }
+        match image(pid) {
+            Ok(image) if image.0 == entry.executable && image != entry.previous => {}
+            _ => return true,
+        }
+        tracked.insert(tgid, (entry.start_time, entry.identity.clone()));
+        false
+    });
+}
+
+/// The image process `pid` runs
+fn image(pid: u32) -> Result<Image, crate::Error> {
+    let exe = metadata(format!("/proc/{pid}/exe"))
+        .context("Could not inspect the executable of the process")
+        .map_err(attach(Errno::ESRCH))?;
+    let arg_start = stat(pid)?.arg_start.unwrap_or_default();
+    Ok(((exe.dev(), exe.ino()), arg_start))
+}
+
+/// The mask of capability `cap`
+fn bit(cap: u32) -> u64 {
+    1_u64.checked_shl(cap).unwrap_or_default()
+}
+
+/// `setre[ug]id(2)`: without the capability the real id may be set to the real or effective one,
+/// and the effective id to any of the real, effective or saved ones
+fn set_re(
+    old: Ids,
+    real: Option<u32>,
+    effective: Option<u32>,
+    capable: bool,
+) -> Result<Ids, Errno> {
+    let mut new = old;
+    if let Some(real) = real {
+        if !capable && real != old.real && real != old.effective {
+            return Err(Errno::EPERM);
+        }
+        new.real = real;
+    }
+    if let Some(effective) = effective {
+        if !capable && !old.contains(effective) {
+            return Err(Errno::EPERM);
+        }
+        new.effective = effective;
+    }
+    if real.is_some() || effective.is_some_and(|effective| effective != old.real) {
+        new.saved = new.effective;
+    }
+    new.fs = new.effective;
+    Ok(new)
+}
+
+/// `setres[ug]id(2)`: without the capability every id may only be set to one of the real, effective or saved ones
+fn set_res(
+    old: Ids,
+    real: Option<u32>,
+    effective: Option<u32>,
+    saved: Option<u32>,
+    capable: bool,
+) -> Result<Ids, Errno> {
+    if !capable
+        && [real, effective, saved]
+            .into_iter()
+            .flatten()
+            .any(|id| !old.contains(id))
+    {
+        return Err(Errno::EPERM);
+    }
+    let effective = effective.unwrap_or(old.effective);
+    Ok(Ids {
+        real: real.unwrap_or(old.real),
+        effective,
+        saved: saved.unwrap_or(old.saved),
+        fs: effective,
+    })
+}
+
+/// The thread group id and start time of the process with the thread `pid`
+fn process(pid: u32) -> Result<(i32, u64), crate::Error> {
+    Ok((status(pid)?.tgid, stat(pid)?.starttime))
+}
+
+/// Reads `/proc/<pid>/stat`
+fn stat(pid: u32) -> Result<Stat, crate::Error> {
+    let stat = File::open(format!("/proc/{pid}/stat"))
+        .context("Could not open the stat of the process")
+        .map_err(attach(Errno::ESRCH))?;
+    Stat::from_read(stat)
+        .context("Could not parse the stat of the process")
+        .map_err(attach(Errno::ESRCH))
+}
+
+/// The children of all threads of process `pid`
+fn children(pid: u32) -> Vec<u32> {
+    let Ok(tasks) = read_dir(format!("/proc/{pid}/task")) else {
+        return Vec::new();
+    };
+    tasks
+        .filter_map(Result::ok)
+        .filter_map(|task| read_to_string(task.path().join("children")).ok())
+        .flat_map(|children| {
+            children
+                .split_whitespace()
+                .filter_map(|child| child.parse().ok())
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// The parent of process `pid`, `None` for processes without one
+fn parent(pid: u32) -> Option<u32> {
+    let ppid = status(pid).ok()?.ppid;
+    u32::try_from(ppid).ok().filter(|&ppid| ppid != 0)
+}
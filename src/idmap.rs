@@ -0,0 +1,96 @@
+//! Id mappings of user namespaces as found in `/proc/<pid>/uid_map` and `/proc/<pid>/gid_map`
+//!
+//! The daemon does not join the user namespace of the container,
+//! so ids returned by the kernel have to be translated the same way the kernel would for the container.
+use std::fs::read_to_string;
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{Context, Error, Result};
+
+/// Id the kernel reports for ids that are not mapped into a user namespace, see `/proc/sys/kernel/overflowuid`
+pub const OVERFLOW_ID: u32 = 65534;
+
+/// A contiguous range of mapped ids
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Extent {
+    /// First id inside the user namespace
+    inside: u32,
+    /// First id outside the user namespace
+    outside: u32,
+    /// Number of mapped ids
+    count: u32,
+}
+
+/// Mapping between the ids inside and outside of a user namespace, see `user_namespaces(7)`
+///
+/// # Examples
+/// ```
+/// use subuidless::idmap::IdMap;
+///
+/// let map: IdMap = "         0       1000          1\n".parse().unwrap();
+/// assert_eq!(map.to_inside(1000), Some(0));
+/// assert_eq!(map.to_inside(0), None);
+/// assert_eq!(map.to_outside(0), Some(1000));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IdMap {
+    extents: Vec<Extent>,
+}
+
+impl IdMap {
+    /// Reads a map file like `/proc/<pid>/uid_map`
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        read_to_string(path)
+            .with_context(|| format!("Could not read {}", path.display()))?
+            .parse()
+    }
+
+    /// Translates an id of the parent namespace into the user namespace
+    #[must_use]
+    pub fn to_inside(&self, id: u32) -> Option<u32> {
+        self.extents.iter().find_map(|extent| {
+            let offset = id
+                .checked_sub(extent.outside)
+                .filter(|&offset| offset < extent.count)?;
+            extent.inside.checked_add(offset)
+        })
+    }
+
+    /// Translates an id of the user namespace into the parent namespace
+    #[must_use]
+    pub fn to_outside(&self, id: u32) -> Option<u32> {
+        self.extents.iter().find_map(|extent| {
+            let offset = id
+                .checked_sub(extent.inside)
+                .filter(|&offset| offset < extent.count)?;
+            extent.outside.checked_add(offset)
+        })
+    }
+}
+
+impl FromStr for IdMap {
+    type Err = Error;
+
+    fn from_str(map: &str) -> Result<Self> {
+        let extents = map
+            .lines()
+            .map(|line| {
+                let mut fields = line.split_whitespace().map(u32::from_str);
+                let mut field = || {
+                    fields
+                        .next()
+                        .context("Id map line has less than 3 fields")?
+                        .context("Invalid id in id map")
+                };
+                Ok(Extent {
+                    inside: field()?,
+                    outside: field()?,
+                    count: field()?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { extents })
+    }
+}
@@ -0,0 +1,162 @@
+//! Emulated owners of the System V IPC objects of a container
+//!
+//! The kernel records the real ids of the owner and creator of shared memory, semaphore sets and message queues in `struct ipc_perm`.
+//! Objects created by an emulated `shmget(2)`, `semget(2)` or `msgget(2)` are owned by the emulated effective ids of the caller instead,
+//! which the daemon keeps by IPC namespace, kind and id of the object.
+//! The objects are only reachable from inside of their IPC namespace,
+//! so the daemon issues the IPC syscalls with `nsenter::in_namespace` in the user and IPC namespaces of the caller.
+use std::collections::BTreeMap;
+use std::fs::metadata;
+use std::os::unix::fs::MetadataExt;
+use std::ptr;
+use std::sync::{Mutex, PoisonError};
+
+use anyhow::Context;
+use nix::errno::Errno;
+use nix::libc::{self, c_int, c_long, gid_t, ipc_perm, key_t, pid_t, uid_t};
+
+use crate::error::attach;
+use crate::mem::Plain;
+
+/// The IPC namespace of a process, identified by the device and inode of `/proc/<pid>/ns/ipc`
+pub type Namespace = (u64, u64);
+
+/// Emulated owners by IPC namespace, kind and id of the object
+static OWNERS: Mutex<BTreeMap<(Namespace, Kind, c_int), Owner>> = Mutex::new(BTreeMap::new());
+
+/// The kind of a System V IPC object
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[allow(clippy::exhaustive_enums)]
+pub enum Kind {
+    /// Shared memory segment of `shmget(2)`
+    SharedMemory,
+    /// Semaphore set of `semget(2)`
+    Semaphores,
+    /// Message queue of `msgget(2)`
+    MessageQueue,
+}
+
+impl Kind {
+    /// The `*_STAT` command, which stats the object at an index of the kernel instead of an id
+    #[must_use]
+    pub fn stat_index(self) -> c_int {
+        match self {
+            Self::SharedMemory => 13,
+            Self::Semaphores => 18,
+            Self::MessageQueue => 11,
+        }
+    }
+
+    /// The `*_STAT_ANY` command, `*_STAT` without the read permission check
+    #[must_use]
+    pub fn stat_any(self) -> c_int {
+        match self {
+            Self::SharedMemory => 15,
+            Self::Semaphores => 20,
+            Self::MessageQueue => 13,
+        }
+    }
+
+    /// `shmget(2)`, `semget(2)` or `msgget(2)` of the object with `key`,
+    /// `arg` is the size of shared memory and the number of semaphores of a semaphore set
+    #[must_use]
+    pub fn get(self, key: key_t, arg: u64, flags: c_int) -> c_long {
+        match self {
+            #[allow(unsafe_code)]
+            // SAFETY:
+            // The syscall only takes integer arguments
+            Self::SharedMemory => unsafe { libc::syscall(libc::SYS_shmget, key, arg, flags) },
+            #[allow(unsafe_code)]
+            // SAFETY:
+            // See `shmget`
+            Self::Semaphores => unsafe { libc::syscall(libc::SYS_semget, key, arg, flags) },
+            #[allow(unsafe_code)]
+            // SAFETY:
+            // See `shmget`
+            Self::MessageQueue => unsafe { libc::syscall(libc::SYS_msgget, key, flags) },
+        }
+    }
+
+    /// `shmctl(2)`, `semctl(2)` or `msgctl(2)` with `cmd` on the object `id`,
+    /// `value` is the `shmid_ds`, `semid_ds` or `msqid_ds` the kernel reads or writes
+    pub fn control<T: Plain>(self, id: c_int, cmd: c_int, value: &mut T) -> c_long {
+        let value = ptr::from_mut(value);
+        match self {
+            #[allow(unsafe_code)]
+            // SAFETY:
+            // `value` is valid for the struct of the kind, which the kernel reads or writes at most
+            Self::SharedMemory => unsafe { libc::syscall(libc::SYS_shmctl, id, cmd, value) },
+            #[allow(unsafe_code)]
+            // SAFETY:
+            // See `shmctl`, the semaphore number is not used by the commands with a struct
+            Self::Semaphores => unsafe { libc::syscall(libc::SYS_semctl, id, 0_i32, cmd, value) },
+            #[allow(unsafe_code)]
+            // SAFETY:
+            // See `shmctl`
+            Self::MessageQueue => unsafe { libc::syscall(libc::SYS_msgctl, id, cmd, value) },
+        }
+    }
+}
+
+/// The emulated owner and creator of an object
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::exhaustive_structs)]
+pub struct Owner {
+    /// User id of the owner
+    pub uid: uid_t,
+    /// Group id of the owner
+    pub gid: gid_t,
+    /// User id of the creator
+    pub cuid: uid_t,
+    /// Group id of the creator
+    pub cgid: gid_t,
+    /// Pid of the creating process in its PID namespace, instead of the one of the child that created the object
+    pub cpid: pid_t,
+}
+
+impl Owner {
+    /// The emulated owner of the object `id` in `namespace`, `None` if the kernel reports it
+    #[must_use]
+    pub fn get(namespace: Namespace, kind: Kind, id: c_int) -> Option<Self> {
+        OWNERS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&(namespace, kind, id))
+            .copied()
+    }
+
+    /// Whether any object of `kind` in `namespace` has an emulated owner
+    #[must_use]
+    pub fn any(namespace: Namespace, kind: Kind) -> bool {
+        OWNERS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .keys()
+            .any(|&(other, other_kind, _)| other == namespace && other_kind == kind)
+    }
+
+    /// Emulates `owner` for the object `id` in `namespace`, `None` forgets the object
+    pub fn save(namespace: Namespace, kind: Kind, id: c_int, owner: Option<Self>) {
+        let mut owners = OWNERS.lock().unwrap_or_else(PoisonError::into_inner);
+        match owner {
+            Some(owner) => owners.insert((namespace, kind, id), owner),
+            None => owners.remove(&(namespace, kind, id)),
+        };
+    }
+
+    /// Replaces the ids of `perm` with the emulated ones
+    pub fn apply(&self, perm: &mut ipc_perm) {
+        perm.uid = self.uid;
+        perm.gid = self.gid;
+        perm.cuid = self.cuid;
+        perm.cgid = self.cgid;
+    }
+}
+
+/// The IPC namespace of process `pid`
+pub fn namespace(pid: u32) -> Result<Namespace, crate::Error> {
+    let namespace = metadata(format!("/proc/{pid}/ns/ipc"))
+        .context("Could not inspect the IPC namespace of the process")
+        .map_err(attach(Errno::ESRCH))?;
+    Ok((namespace.dev(), namespace.ino()))
+}
@@ -1,10 +1,13 @@
 //! Subuidless
 
+// The code generated by `#[syscall]` refers to `::subuidless`, which has to resolve in this crate as well
+extern crate self as subuidless;
+
 use std::env::var;
 use std::fs::remove_file;
 use std::io::ErrorKind;
 use std::os::unix::net::UnixListener;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
 
@@ -15,21 +18,72 @@ mod proto {
     include!(concat!(env!("OUT_DIR"), "/protos/mod.rs"));
 }
 
+/// Architectures and struct layouts of the supported syscall ABIs
+pub mod abi;
+/// POSIX ACLs in their xAttribute representation
+pub mod acl;
+/// Injects file descriptors into the calling process
+pub mod addfd;
+/// Verifies the credentials of connecting clients
+pub mod auth;
+/// Configuration file of the daemon
+pub mod config;
+/// Credentials of the calling process and permission checks
+pub mod creds;
 /// Provides `SyscallError` used to attach an `Errno` to an `Error` which is then returned to the Caller
 pub mod error;
 /// Type Alies for `SyscallErrno` for ease of use.
 pub type Error = SyscallErrno;
 
+/// Emulated user and group ids and capabilities of the processes
+pub mod identity;
+
+/// Id mappings of user namespaces
+pub mod idmap;
+
+/// Emulated owners of System V IPC objects
+pub mod ipc;
+
+/// Runs operations in the namespaces of the calling process
+pub mod nsenter;
+
 /// Contains `MaybeRemote` to work with the Arguments provided by Seccomp
 pub mod mem;
 
-/// Provides the `syscall!` Macro to ease the implementation of new Syscalls
+/// Translates host pids into the PID namespace of a container
+pub mod pidns;
+
+/// Thread pool running the syscall handlers
+pub mod pool;
+
+/// Tracks worker processes and handles shutdown signals
+pub mod supervisor;
+
+/// Reads and writes the memory of the calling process
+pub mod remote;
+
+/// Resolves the paths passed to syscalls into `O_PATH` file descriptors
+pub mod resolve;
+
+/// Provides the `Syscall` trait implemented by the handlers of the emulated Syscalls
 pub mod syscall;
+/// Implements a new Syscall for a handler function, see `subuidless_macros::syscall`
+#[allow(clippy::useless_attribute, clippy::pub_use)] // The re-export is the path handlers use
+pub use subuidless_macros::syscall;
+/// Dependencies of the code generated by `#[syscall]`, so crates using it do not need to depend on them
+#[doc(hidden)]
+pub mod __private {
+    #[allow(clippy::useless_attribute, clippy::pub_use)] // Referred to by the generated code
+    pub use {anyhow, inventory, libseccomp};
+}
+/// Socket activation and readiness notification for systemd
+pub mod systemd;
 /// Helper Methods to modify the rootlesscontaine.rs xAttribute
 /// <https://github.com/rootless-containers/proto>
 pub mod xattr;
 
 /// Creates the Unix Socket `subuidless.socket` at `$XDG_RUNTIME_DIR`  
+/// If the process was socket activated by systemd, the inherited socket is used instead  
 /// Fails if `$XDG_RUNTIME_DIR` is not set
 ///
 /// # Examples
@@ -65,11 +119,22 @@ pub mod xattr;
 /// }
 /// ```
 pub fn create_socket() -> Result<UnixListener> {
+    systemd::listen_fd()?.map_or_else(|| create_socket_at(default_socket_path()?), Ok)
+}
+
+/// Default location of the socket: `$XDG_RUNTIME_DIR/subuidless.sock`  
+/// Fails if `$XDG_RUNTIME_DIR` is not set
+pub fn default_socket_path() -> Result<PathBuf> {
     let xdg_runtime_dir =
         var("XDG_RUNTIME_DIR").context("Must specify XDG_RUNTIME_DIR for socket Path")?;
-    let socket_path = Path::new(&xdg_runtime_dir).join("subuidless.sock");
+    Ok(Path::new(&xdg_runtime_dir).join("subuidless.sock"))
+}
+
+/// Creates the Unix Socket at `socket_path`, replacing a stale socket left behind by a previous run
+pub fn create_socket_at<P: AsRef<Path>>(socket_path: P) -> Result<UnixListener> {
+    let socket_path = socket_path.as_ref();
 
-    if let Err(err) = remove_file(&socket_path) {
+    if let Err(err) = remove_file(socket_path) {
         if err.kind() == ErrorKind::NotFound {
             Ok(())
         } else {
@@ -1,101 +1,515 @@
-//!
+//! `subuidless` daemon and its command-line interface
 use std::collections::HashMap;
+use std::fs::{remove_file, set_permissions, Permissions};
+use std::io::{self, stdout, Write};
+use std::num::NonZeroUsize;
 use std::ops::Neg;
-use std::os::fd::RawFd;
+use std::os::fd::{AsFd, AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::UnixStream;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process;
 use std::sync::Arc;
+use std::thread::{available_parallelism, sleep};
+use std::time::Duration;
 
-use anyhow::Context;
-use libseccomp::{ScmpFd, ScmpNotifReq, ScmpNotifResp, ScmpNotifRespFlags, ScmpSyscall};
+use anyhow::{anyhow, ensure, Context};
+use clap::{ArgAction, Args, Parser, Subcommand};
+use libseccomp::{ScmpArch, ScmpNotifReq, ScmpNotifResp, ScmpNotifRespFlags, ScmpSyscall};
+use log::{debug, info, warn, LevelFilter};
 use nix::errno::Errno;
-use nix::sched::{setns, unshare, CloneFlags};
-use nix::unistd::fork;
+use nix::libc::{uid_t, EPERM, ESRCH};
+use nix::sys::prctl::set_pdeathsig;
+use nix::sys::signal::Signal;
+use nix::unistd::daemon;
+use rustix::event::{poll, PollFd, PollFlags};
 use rustix::process as rpr;
-use rustix::process::pidfd_open;
 use sendfd::RecvWithFd;
+use serde_json::Value;
 
-use subuidless::create_socket;
+use subuidless::auth::{authenticate, ensure_alive, open_target};
+use subuidless::config::{parse_mode, Config, OutsidePolicy};
 use subuidless::error::attach;
-use subuidless::syscall::Syscall;
+use subuidless::pidns::PidNamespace;
+use subuidless::pool::Pool;
+use subuidless::supervisor::{shutdown_signals, Event, Supervisor};
+use subuidless::syscall::{handlers, is_stale, registry, respond, Notification, Outcome, Syscall};
+use subuidless::xattr::get_xa_user;
+use subuidless::{create_socket_at, default_socket_path, systemd};
+
+/// Pause after `accept(2)` failed temporarily, so a lack of file descriptors does not spin the daemon
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Handlers of the emulated syscalls
+type Registry = HashMap<(ScmpArch, ScmpSyscall), &'static dyn Syscall>;
+
+/// Rootless Containers without `/etc/subuid` and `/etc/subgid`
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Cli {
+    /// JSON configuration file, command-line options take precedence
+    #[arg(short, long, global = true)]
+    config: Option<PathBuf>,
+    /// Increase the log verbosity, can be repeated
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    verbose: u8,
+    /// Decrease the log verbosity, can be repeated
+    #[arg(short, long, action = ArgAction::Count, global = true, conflicts_with = "verbose")]
+    quiet: u8,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Accept containers on the socket and emulate their syscalls (default)
+    Serve(ServeArgs),
+    /// Print a seccomp profile that forwards the emulated syscalls to the socket
+    Profile(ProfileArgs),
+    /// Print the emulated owner of files
+    Inspect(InspectArgs),
+}
+
+#[derive(Debug, Default, Args)]
+struct ServeArgs {
+    /// Path of the socket [default: `$XDG_RUNTIME_DIR/subuidless.sock`]
+    #[arg(short, long)]
+    socket: Option<PathBuf>,
+    /// Permissions of the socket in octal notation
+    #[arg(short = 'm', long, value_parser = parse_mode)]
+    socket_mode: Option<u32>,
+    /// Detach from the terminal and run in the background
+    #[arg(short, long)]
+    daemon: bool,
+    /// Stay attached to the terminal (default)
+    #[arg(short, long, conflicts_with = "daemon")]
+    foreground: bool,
+    /// On shutdown, let the workers answer their pending notifications before exiting
+    #[arg(short, long)]
+    wait: bool,
+    /// Number of threads handling the notifications of each container [default: number of CPUs]
+    #[arg(short, long)]
+    threads: Option<NonZeroUsize>,
+    /// Uid allowed to connect to the socket, can be repeated [default: uid of the daemon]
+    #[arg(short = 'u', long = "allow-uid")]
+    allowed_uids: Vec<uid_t>,
+}
+
+#[derive(Debug, Args)]
+struct ProfileArgs {
+    /// Path of the socket written to `listenerPath` [default: `$XDG_RUNTIME_DIR/subuidless.sock`]
+    #[arg(short, long)]
+    socket: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct InspectArgs {
+    /// Files to inspect
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+    /// Inspect symbolic links themselves instead of their targets
+    #[arg(short = 'P', long)]
+    no_dereference: bool,
+}
 
 fn main() -> anyhow::Result<()> {
-    let mut syscalls = HashMap::new();
+    let cli = Cli::parse();
+    let config = cli
+        .config
+        .as_ref()
+        .map(Config::load)
+        .transpose()?
+        .unwrap_or_default();
+
+    env_logger::Builder::new()
+        .filter_level(log_level(
+            config.log_level.unwrap_or(LevelFilter::Info),
+            cli.verbose,
+            cli.quiet,
+        ))
+        .parse_default_env()
+        .init();
 
-    for syscall in inventory::iter::<&dyn Syscall> {
-        syscalls.insert(syscall.get_syscall()?, *syscall);
+    match cli.command {
+        None => serve(&config, &ServeArgs::default()),
+        Some(Command::Serve(args)) => serve(&config, &args),
+        Some(Command::Profile(args)) => profile(&config, &args),
+        Some(Command::Inspect(args)) => inspect(&args),
     }
-    let syscalls = Arc::new(syscalls);
-    let listener = create_socket()?;
-    loop {
-        let (unix_stream, _socket_address) = listener.accept()?;
-
-        let mut fd: [RawFd; 1] = [0; 1];
-        let mut data = vec![0; 4096];
-        let (size, _fd) = unix_stream.recv_with_fd(&mut data, &mut fd)?;
-
-        data.truncate(size);
-        data.shrink_to_fit();
-
-        let json: serde_json::Value = serde_json::from_slice(&data)?;
-        let pid = json
-            .get("pid")
-            .and_then(serde_json::Value::as_u64)
-            .and_then(|pid| i32::try_from(pid).ok())
-            .and_then(rpr::Pid::from_raw)
-            .context("Could not get Container pid")?;
-
-        #[allow(unsafe_code)]
-        // SAFETY:
-        // Process is no multithreaded. Each Connection spawns a new child that gets moved into the "container"
-        if unsafe { fork() }?.is_parent() {
-            continue;
+}
+
+/// Moves `base` up by `verbose` and down by `quiet` levels
+fn log_level(base: LevelFilter, verbose: u8, quiet: u8) -> LevelFilter {
+    let base = LevelFilter::iter()
+        .position(|level| level == base)
+        .unwrap_or_default();
+    LevelFilter::iter()
+        .nth(
+            base.saturating_add(verbose.into())
+                .saturating_sub(quiet.into()),
+        )
+        .unwrap_or(LevelFilter::Trace)
+}
+
+fn socket_path(config: &Config, socket: Option<&PathBuf>) -> anyhow::Result<PathBuf> {
+    socket
+        .or(config.socket.as_ref())
+        .cloned()
+        .map_or_else(default_socket_path, Ok)
+}
+
+/// `path` with its parent directory canonicalized, the socket itself may not exist yet
+fn absolute(path: &Path) -> anyhow::Result<PathBuf> {
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("{} is not a socket path", path.display()))?;
+    let parent = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let parent = parent
+        .canonicalize()
+        .with_context(|| format!("Could not resolve {}", parent.display()))?;
+    Ok(parent.join(file_name))
+}
+
+fn serve(config: &Config, args: &ServeArgs) -> anyhow::Result<()> {
+    let syscalls = Arc::new(registry(config)?);
+    let (listener, socket_path) = if let Some(listener) = systemd::listen_fd()? {
+        info!("Listening on the socket passed by systemd");
+        (listener, None)
+    } else {
+        // `--daemon` changes the working directory to `/`, where a relative path could not be removed
+        let socket_path = absolute(&socket_path(config, args.socket.as_ref())?)?;
+        let listener = create_socket_at(&socket_path)?;
+        if let Some(mode) = args.socket_mode.or(config.socket_mode) {
+            set_permissions(&socket_path, Permissions::from_mode(mode))
+                .context("Could not set the socket permissions")?;
         }
+        info!("Listening on {}", socket_path.display());
+        (listener, Some(socket_path))
+    };
+
+    if args.daemon {
+        daemon(false, false).context("Could not detach from the terminal")?;
+    }
+    let mut supervisor = Supervisor::new()?;
+    systemd::notify(&format!(
+        "READY=1\nMAINPID={}\nSTATUS=Accepting containers",
+        process::id()
+    ))?;
+
+    let allowed_uids = if args.allowed_uids.is_empty() {
+        config
+            .allowed_uids
+            .clone()
+            .unwrap_or_else(|| vec![rpr::getuid().as_raw()])
+    } else {
+        args.allowed_uids.clone()
+    };
+
+    let threads = args
+        .threads
+        .or(config.threads)
+        .map_or_else(available_parallelism, Ok)
+        .context("Could not determine the number of CPUs")?;
+
+    let daemon_pid = rpr::getpid();
+    let stopped = loop {
+        match supervisor.wait(Some(listener.as_fd())) {
+            Ok(Event::Ready) => {}
+            Ok(Event::Shutdown(signal)) => break Ok(signal),
+            Ok(Event::Exited(_)) => continue,
+            Err(err) => break Err(err),
+        }
+
+        let unix_stream = match listener.accept() {
+            Ok((unix_stream, _socket_address)) => unix_stream,
+            Err(err) if is_transient(&err) => {
+                warn!("Could not accept a connection: {err}");
+                // The listener stays readable while the process is out of resources
+                sleep(ACCEPT_BACKOFF);
+                continue;
+            }
+            Err(err) => break Err(err).context("Could not accept a connection"),
+        };
+
+        let container = match accept_container(&unix_stream, &allowed_uids) {
+            Ok(container) => container,
+            Err(err) => {
+                warn!("Rejected connection: {err:#}");
+                continue;
+            }
+        };
+        info!("Accepted container {}", container.pid.as_raw_nonzero());
+
+        if supervisor.fork()?.is_none() {
+            drop(supervisor);
+            drop(listener);
+            return run_worker(&container, &syscalls, threads, config, daemon_pid);
+        }
+    };
+
+    match stopped.as_ref() {
+        Ok(signal) => info!(
+            "Received {signal}, stopping {} workers",
+            supervisor.workers()
+        ),
+        // The error is reported once the daemon cleaned up
+        Err(_err) => warn!("Stopping {} workers after an error", supervisor.workers()),
+    }
+    systemd::notify("STOPPING=1")?;
+    supervisor.shutdown(args.wait)?;
+
+    if let Some(socket_path) = socket_path {
+        remove_file(socket_path).context("Could not remove the socket")?;
+    }
+    stopped.map(|_signal| ())
+}
+
+/// Whether `accept(2)` failed because of the connection or a temporary lack of resources, instead of the listener
+fn is_transient(err: &io::Error) -> bool {
+    matches!(
+        err.raw_os_error().map(Errno::from_raw),
+        Some(
+            Errno::EINTR
+                | Errno::EAGAIN
+                | Errno::ECONNABORTED
+                | Errno::EPROTO
+                | Errno::EPERM
+                | Errno::EMFILE
+                | Errno::ENFILE
+                | Errno::ENOBUFS
+                | Errno::ENOMEM
+        )
+    )
+}
+
+/// Handles the notifications of the container on a new thread pool
+fn run_worker(
+    container: &Container,
+    syscalls: &Arc<Registry>,
+    threads: NonZeroUsize,
+    config: &Config,
+    daemon_pid: rpr::Pid,
+) -> anyhow::Result<()> {
+    // Workers must not outlive the daemon, which may be killed without shutting them down
+    set_pdeathsig(Signal::SIGKILL)?;
+    ensure!(
+        rpr::getppid() == Some(daemon_pid),
+        "The daemon exited before the worker started"
+    );
+    let pid_ns = PidNamespace::new(container.pid)?;
+    // The namespace was read through the pid, which still refers to the container process only if it is alive
+    ensure_alive(&container.pid_fd, container.pid)?;
+    let pool = Pool::new(threads)?;
+    handle_notifications(container, syscalls, &pool, &pid_ns, config)
+}
+
+/// Receives and answers the notifications of the container.
+/// The handlers run on `pool`, the notifications keep being received while they are busy.
+/// After a shutdown signal the pending notifications are drained before returning.
+fn handle_notifications(
+    container: &Container,
+    syscalls: &Arc<Registry>,
+    pool: &Pool,
+    pid_ns: &PidNamespace,
+    config: &Config,
+) -> anyhow::Result<()> {
+    let mut signals = shutdown_signals()?;
+    let fd = container.notify_fd.as_raw_fd();
+    let pid = container.pid.as_raw_nonzero();
+    let mut draining = false;
 
-        unshare(CloneFlags::CLONE_FS)?;
-        let pid_fd = pidfd_open(pid, rpr::PidfdFlags::empty())?;
-        setns(&pid_fd, CloneFlags::CLONE_NEWUSER)?;
-        setns(&pid_fd, CloneFlags::CLONE_NEWNS)?;
-        setns(&pid_fd, CloneFlags::CLONE_NEWPID)?;
-
-        #[allow(unsafe_code)]
-        // SAFETY:
-        // To get the NSpid from seccomp the process needs to be in the same PID Namespace.
-        // Therefore, we need to create a new Process, because pids remain the same.
-        if unsafe { fork() }?.is_parent() {
+    loop {
+        let (revents, signaled) = {
+            let mut poll_fds = [
+                PollFd::new(&container.notify_fd, PollFlags::IN),
+                PollFd::new(&signals, PollFlags::IN),
+            ];
+            poll(&mut poll_fds, if draining { 0 } else { -1 })?;
+            (poll_fds[0].revents(), !poll_fds[1].revents().is_empty())
+        };
+
+        if signaled && signals.read_signal()?.is_some() {
+            info!("Draining the pending notifications");
+            draining = true;
+        }
+        if !revents.contains(PollFlags::IN) {
+            // The filter has no users left once every process of the container exited
+            if revents.intersects(PollFlags::HUP | PollFlags::ERR | PollFlags::NVAL) {
+                info!("Container {pid} exited, stopping its worker");
+                return Ok(());
+            }
+            if draining {
+                return Ok(());
+            }
             continue;
         }
-        let mut runtime = true;
 
-        loop {
-            let mut notif_req = ScmpNotifReq::receive(fd[0])?;
+        let notif_req = match ScmpNotifReq::receive(fd) {
+            Ok(notif_req) => notif_req,
+            // The syscall was interrupted or the process died after `poll`
+            Err(err) if is_stale(&err) => continue,
+            // The notify fd is no longer usable, like after the container exited
+            Err(err) => {
+                info!("Could not receive notifications of container {pid}, stopping its worker: {err}");
+                return Ok(());
+            }
+        };
 
-            if runtime && i32::try_from(notif_req.pid)? < pid.as_raw_nonzero().get() {
-                runtime = false;
-                if i32::try_from(notif_req.pid)? == pid.as_raw_nonzero().get() {
-                    notif_req.pid = 1;
-                }
+        let translated = i32::try_from(notif_req.pid)
+            .map_err(anyhow::Error::from)
+            .and_then(|host_pid| pid_ns.translate(host_pid));
+        match translated {
+            // Handlers work with the host pid, paths are resolved through `/proc/<pid>/root`
+            Ok(Some(_container_pid)) => {
+                let syscalls = Arc::clone(syscalls);
+                let notification = Notification {
+                    req: notif_req,
+                    fd,
+                    permission_checks: config.permission_checks,
+                };
+                pool.execute(move || handle_scmp_req(notification, &syscalls))?;
+            }
+            Ok(None) => {
+                debug!("Process {} is outside the container", notif_req.pid);
+                respond(fd, outside_response(notif_req.id, config.outside));
+            }
+            Err(err) => {
+                debug!("Could not translate pid {}: {err:#}", notif_req.pid);
+                respond(
+                    fd,
+                    ScmpNotifResp::new_error(
+                        notif_req.id,
+                        ESRCH.neg(),
+                        ScmpNotifRespFlags::empty(),
+                    ),
+                );
             }
-            handle_scmp_req(fd[0], notif_req, &syscalls);
         }
     }
 }
 
-fn handle_scmp_req(fd: ScmpFd, req: ScmpNotifReq, syscalls: &HashMap<ScmpSyscall, &dyn Syscall>) {
-    let syscall = || {
+/// Answers a notification of a process outside the container according to `policy`
+fn outside_response(id: u64, policy: OutsidePolicy) -> ScmpNotifResp {
+    match policy {
+        OutsidePolicy::Deny => {
+            ScmpNotifResp::new_error(id, EPERM.neg(), ScmpNotifRespFlags::empty())
+        }
+        OutsidePolicy::Continue => ScmpNotifResp::new_val(id, 0, ScmpNotifRespFlags::CONTINUE),
+    }
+}
+
+/// A container whose runtime connected to the socket
+struct Container {
+    /// Seccomp notify fd of the container
+    notify_fd: OwnedFd,
+    /// Pid of the container init process
+    pid: rpr::Pid,
+    /// Pidfd of the container init process, which tells whether `pid` still refers to it
+    pid_fd: OwnedFd,
+}
+
+/// Receives the seccomp notify fd and the container state from the runtime connected with `unix_stream`
+fn accept_container(unix_stream: &UnixStream, allowed_uids: &[uid_t]) -> anyhow::Result<Container> {
+    let peer = authenticate(unix_stream, allowed_uids)?;
+
+    let mut fd: [RawFd; 1] = [-1; 1];
+    let mut data = vec![0; 4096];
+    let (size, fd_count) = unix_stream.recv_with_fd(&mut data, &mut fd)?;
+    ensure!(fd_count == 1, "Did not receive the seccomp notify fd");
+
+    #[allow(unsafe_code)]
+    // SAFETY:
+    // The fd was just received with `SCM_RIGHTS`, nothing else in this process refers to it
+    let notify_fd = unsafe { OwnedFd::from_raw_fd(fd[0]) };
+
+    data.truncate(size);
+    data.shrink_to_fit();
+
+    let json: Value = serde_json::from_slice(&data)?;
+    let pid = json
+        .get("pid")
+        .and_then(Value::as_u64)
+        .and_then(|pid| i32::try_from(pid).ok())
+        .and_then(rpr::Pid::from_raw)
+        .context("Could not get Container pid")?;
+    let pid_fd = open_target(&peer, pid)?;
+
+    Ok(Container {
+        notify_fd,
+        pid,
+        pid_fd,
+    })
+}
+
+fn profile(config: &Config, args: &ProfileArgs) -> anyhow::Result<()> {
+    let mut profile: Value = serde_json::from_str(include_str!("../seccomp.json"))?;
+    let socket_path = socket_path(config, args.socket.as_ref())?;
+    let names: Vec<String> = handlers(config).map(Syscall::get_name).collect();
+
+    let profile_object = profile
+        .as_object_mut()
+        .context("Seccomp profile is not an object")?;
+    profile_object.insert(
+        "listenerPath".to_owned(),
+        Value::from(socket_path.to_string_lossy()),
+    );
+
+    let rules = profile_object
+        .get_mut("syscalls")
+        .and_then(Value::as_array_mut)
+        .context("Seccomp profile has no syscalls")?;
+    for rule in rules.iter_mut().filter_map(Value::as_object_mut) {
+        if rule.get("action").and_then(Value::as_str) == Some("SCMP_ACT_NOTIFY") {
+            rule.insert("names".to_owned(), Value::from(names.clone()));
+            continue;
+        }
+        if let Some(rule_names) = rule.get_mut("names").and_then(Value::as_array_mut) {
+            rule_names.retain(|name| {
+                !name
+                    .as_str()
+                    .is_some_and(|name| names.iter().any(|handler| handler == name))
+            });
+        }
+    }
+
+    let mut stdout = stdout().lock();
+    serde_json::to_writer_pretty(&mut stdout, &profile)?;
+    writeln!(stdout)?;
+    Ok(())
+}
+
+fn inspect(args: &InspectArgs) -> anyhow::Result<()> {
+    let mut stdout = stdout().lock();
+    for path in &args.paths {
+        let (uid, gid) = get_xa_user(path, !args.no_dereference)
+            .with_context(|| format!("Could not inspect {}", path.display()))?;
+        writeln!(stdout, "{}\t{uid}\t{gid}", path.display())?;
+    }
+    Ok(())
+}
+
+/// Executes the handler of the syscall and answers the notification.
+/// Panics of the handler are caught, so a broken handler only fails the syscall and not the container.
+fn handle_scmp_req(notification: Notification, syscalls: &Registry) {
+    let req = notification.req;
+    let responder = notification.clone();
+    let syscall = move || {
         let syscall = syscalls
-            .get(&req.data.syscall)
+            .get(&(req.data.arch, req.data.syscall))
             .context("Syscall not supported")
             .map_err(attach(Errno::ENOSYS))?;
+        if syscall.is_permission_check() && !notification.permission_checks {
+            return Ok(Outcome::Continue);
+        }
 
-        syscall.execute(req, fd)
+        catch_unwind(AssertUnwindSafe(|| syscall.execute(notification)))
+            .map_err(|_panic| anyhow!("Handler of {} panicked", syscall.get_name()))
+            .map_err(attach(Errno::EIO))?
     };
 
-    #[allow(clippy::expect_used)]
-    match syscall() {
-        Ok(val) => ScmpNotifResp::new_val(req.id, val, ScmpNotifRespFlags::empty()).respond(fd),
-        Err(err) => {
-            ScmpNotifResp::new_error(req.id, i32::from(err).neg(), ScmpNotifRespFlags::empty())
-                .respond(fd)
-        }
-    }
-    .expect("Could not respond to Seccomp");
+    responder.respond(syscall());
 }
@@ -1,52 +1,167 @@
 use std::convert::TryFrom;
-use std::ffi::c_int;
-use std::fs::{File, OpenOptions};
+use std::ffi::{c_int, OsString};
+use std::fs::File;
+use std::iter::repeat_with;
 use std::marker::PhantomData;
-use std::mem::size_of;
-use std::os::unix::prelude::FileExt;
+use std::mem::{size_of_val, MaybeUninit};
+use std::os::fd::RawFd;
+use std::os::unix::ffi::OsStringExt;
 use std::path::PathBuf;
 use std::slice;
 
 use anyhow::Context;
-use libseccomp::{notify_id_valid, ScmpFd};
+use libseccomp::{notify_id_valid, ScmpArch, ScmpFd};
 use nix::errno::Errno;
 use nix::fcntl::{AtFlags, OFlag};
-use nix::libc::{mode_t, stat};
+use nix::libc::{mode_t, msqid_ds, semid_ds, shmid_ds, stat, ucred, AT_FDCWD};
 use nix::sys::stat::Mode;
-use nix::unistd::Pid;
+use nix::unistd::{AccessFlags, Pid};
 
+use crate::abi::{is_32bit, Iovec32, Iovec64, Msghdr32, Msghdr64};
 use crate::error::attach;
+use crate::remote;
 
 const PATH_MAX: usize = 4096;
+/// Maximum length of the name of an xAttribute, see `XATTR_NAME_MAX`
+const XATTR_NAME_MAX: usize = 255;
 
 #[derive(Copy, Clone, Debug)]
 /// Newtype for values provided in the `args: [u64;6]` array from Seccomp
 /// Int values like Flags can be used instantly whereas values like Strings need to be read form the callers memory
 pub struct MaybeRemote {
     pid: Pid,
+    arch: ScmpArch,
     pointer: u64,
     fd: ScmpFd,
     id: u64,
 }
 
 impl MaybeRemote {
-    /// Create a new `MaybeRemote`
+    /// Create a new `MaybeRemote` for an argument passed by a process using the ABI of `arch`
+    /// On 32-bit ABIs only the lower 32 bits of the argument are used
     #[must_use]
-    pub fn new(pid: Pid, pointer: u64, fd: ScmpFd, id: u64) -> Self {
+    pub fn new(pid: Pid, arch: ScmpArch, pointer: u64, fd: ScmpFd, id: u64) -> Self {
+        let pointer = if is_32bit(arch) {
+            pointer & 0xffff_ffff
+        } else {
+            pointer
+        };
         Self {
             pid,
+            arch,
             pointer,
             fd,
             id,
         }
     }
+
+    /// Architecture of the ABI used by the Caller
+    #[must_use]
+    pub fn arch(&self) -> ScmpArch {
+        self.arch
+    }
+
+    /// The argument as a signed `long` of the Caller, sign extended on 32-bit ABIs
+    ///
+    /// # Examples
+    /// ```
+    /// use libseccomp::ScmpArch;
+    /// use nix::unistd::Pid;
+    /// use subuidless::mem::MaybeRemote;
+    ///
+    /// let compat = MaybeRemote::new(Pid::this(), ScmpArch::X86, 0xffff_ff9c, -1, 0);
+    /// assert_eq!(compat.signed(), -100);
+    /// let native = MaybeRemote::new(Pid::this(), ScmpArch::X8664, 0xffff_ff9c, -1, 0);
+    /// assert_eq!(native.signed(), 0xffff_ff9c);
+    /// ```
+    #[must_use]
+    pub fn signed(&self) -> i64 {
+        let value = 0_i64.wrapping_add_unsigned(self.pointer);
+        if is_32bit(self.arch) && value > i64::from(i32::MAX) {
+            value.wrapping_sub(0x1_0000_0000)
+        } else {
+            value
+        }
+    }
+
+    /// The lower 32 bits, as used by the kernel for `int` and `unsigned int` arguments on every ABI
+    fn low32(&self) -> u32 {
+        u32::try_from(self.pointer & 0xffff_ffff).unwrap_or_default()
+    }
+
+    /// The lower 32 bits as a C `int`
+    fn int(&self) -> c_int {
+        0_i32.wrapping_add_unsigned(self.low32())
+    }
+
+    /// Runs `access` between two `notify_id_valid` checks, so the memory surely belonged to the Caller
+    fn checked<T>(
+        &self,
+        access: impl FnOnce(&Self) -> Result<T, crate::Error>,
+    ) -> Result<T, crate::Error> {
+        notify_id_valid(self.fd, self.id).map_err(attach(Errno::EPERM))?;
+        let result = access(self)?;
+        notify_id_valid(self.fd, self.id).map_err(attach(Errno::EPERM))?;
+        Ok(result)
+    }
 }
 
+impl TryFrom<MaybeRemote> for usize {
+    type Error = crate::Error;
+
+    fn try_from(value: MaybeRemote) -> Result<Self, Self::Error> {
+        usize::try_from(value.pointer).map_err(attach(Errno::EINVAL))
+    }
+}
+
+/// An `unsigned int`, `uid_t` or `gid_t`, the kernel only uses the lower 32 bits
 impl TryFrom<MaybeRemote> for u32 {
     type Error = crate::Error;
 
     fn try_from(value: MaybeRemote) -> Result<Self, Self::Error> {
-        u32::try_from(value.pointer).map_err(attach(Errno::EINVAL))
+        Ok(value.low32())
+    }
+}
+
+/// An `int` or `pid_t`, the kernel only uses the lower 32 bits
+impl TryFrom<MaybeRemote> for i32 {
+    type Error = crate::Error;
+
+    fn try_from(value: MaybeRemote) -> Result<Self, Self::Error> {
+        Ok(value.int())
+    }
+}
+
+/// A `uid_t` or `gid_t` that is `None` for `-1`, which leaves the id unchanged, e.g. in `chown(2)`
+///
+/// # Examples
+/// ```
+/// use libseccomp::ScmpArch;
+/// use nix::unistd::Pid;
+/// use subuidless::mem::MaybeRemote;
+///
+/// fn main() -> anyhow::Result<()> {
+///     let sign_extended = MaybeRemote::new(Pid::this(), ScmpArch::X8664, u64::MAX, -1, 0);
+///     assert_eq!(Option::<u32>::try_from(sign_extended)?, None);
+///     let upper_bits = MaybeRemote::new(Pid::this(), ScmpArch::X8664, 0x1_0000_03e8, -1, 0);
+///     assert_eq!(Option::<u32>::try_from(upper_bits)?, Some(1000));
+///     Ok(())
+/// }
+/// ```
+impl TryFrom<MaybeRemote> for Option<u32> {
+    type Error = crate::Error;
+
+    fn try_from(value: MaybeRemote) -> Result<Self, Self::Error> {
+        Ok(Some(value.low32()).filter(|&id| id != u32::MAX))
+    }
+}
+
+/// A `pid_t`
+impl TryFrom<MaybeRemote> for Pid {
+    type Error = crate::Error;
+
+    fn try_from(value: MaybeRemote) -> Result<Self, Self::Error> {
+        Ok(Pid::from_raw(value.int()))
     }
 }
 
@@ -54,7 +169,7 @@ impl TryFrom<MaybeRemote> for AtFlags {
     type Error = crate::Error;
 
     fn try_from(value: MaybeRemote) -> Result<Self, Self::Error> {
-        AtFlags::from_bits(c_int::try_from(value.pointer).map_err(attach(Errno::EINVAL))?)
+        AtFlags::from_bits(value.int())
             .context("Could not convert to bits")
             .map_err(attach(Errno::EINVAL))
     }
@@ -64,68 +179,150 @@ impl TryFrom<MaybeRemote> for OFlag {
     type Error = crate::Error;
 
     fn try_from(value: MaybeRemote) -> Result<Self, Self::Error> {
-        OFlag::from_bits(c_int::try_from(value.pointer).map_err(attach(Errno::EINVAL))?)
+        OFlag::from_bits(value.int())
             .context("Could not convert to bits")
             .map_err(attach(Errno::EINVAL))
     }
 }
 
-impl TryFrom<MaybeRemote> for Mode {
+impl TryFrom<MaybeRemote> for AccessFlags {
     type Error = crate::Error;
 
     fn try_from(value: MaybeRemote) -> Result<Self, Self::Error> {
-        Mode::from_bits(mode_t::try_from(value.pointer).map_err(attach(Errno::EINVAL))?)
+        AccessFlags::from_bits(value.int())
             .context("Could not convert to bits")
             .map_err(attach(Errno::EINVAL))
     }
 }
 
-impl TryFrom<MaybeRemote> for Option<File> {
+impl TryFrom<MaybeRemote> for Mode {
     type Error = crate::Error;
 
     fn try_from(value: MaybeRemote) -> Result<Self, Self::Error> {
-        let Ok(fd) = i32::try_from(value.pointer) else {
+        Mode::from_bits(mode_t::from(value.low32()))
+            .context("Could not convert to bits")
+            .map_err(attach(Errno::EINVAL))
+    }
+}
+
+/// Types a file descriptor argument can be converted to, used for `#[fd]` arguments of `#[syscall]`
+///
+/// Only the lower 32 bits are used, `AT_FDCWD` is accepted by the `Option` types only,
+/// other negative values fail with `EBADF`.
+pub trait FromFd: Sized {
+    /// Converts the file descriptor argument `value`
+    fn from_fd(value: MaybeRemote) -> Result<Self, crate::Error>;
+}
+
+/// The number of a file descriptor in the calling process, `None` for `AT_FDCWD`
+impl FromFd for Option<RawFd> {
+    fn from_fd(value: MaybeRemote) -> Result<Self, crate::Error> {
+        match value.int() {
+            AT_FDCWD => Ok(None),
+            fd if fd < 0 => Err(Errno::EBADF.into()),
+            fd => Ok(Some(fd)),
+        }
+    }
+}
+
+/// The number of a file descriptor in the calling process
+impl FromFd for RawFd {
+    fn from_fd(value: MaybeRemote) -> Result<Self, crate::Error> {
+        Ok(Option::<RawFd>::from_fd(value)?.ok_or(Errno::EBADF)?)
+    }
+}
+
+/// The file behind a file descriptor of the calling process, `None` for `AT_FDCWD`
+impl FromFd for Option<File> {
+    fn from_fd(value: MaybeRemote) -> Result<Self, crate::Error> {
+        let Some(fd) = Option::<RawFd>::from_fd(value)? else {
             return Ok(None);
         };
         let file =
-            File::open(format!("/proc/{}/fd/{}", value.pid, fd)).map_err(attach(Errno::EBADFD))?;
+            File::open(format!("/proc/{}/fd/{}", value.pid, fd)).map_err(attach(Errno::EBADF))?;
         notify_id_valid(value.fd, value.id).map_err(attach(Errno::EPERM))?;
         Ok(Some(file))
     }
 }
 
+/// The file behind a file descriptor of the calling process
+impl FromFd for File {
+    fn from_fd(value: MaybeRemote) -> Result<Self, crate::Error> {
+        Ok(Option::<File>::from_fd(value)?.ok_or(Errno::EBADF)?)
+    }
+}
+
+/// See `FromFd`
+impl TryFrom<MaybeRemote> for Option<File> {
+    type Error = crate::Error;
+
+    fn try_from(value: MaybeRemote) -> Result<Self, Self::Error> {
+        Self::from_fd(value)
+    }
+}
+
+/// See `FromFd`
+///
+/// # Examples
+/// ```
+/// use std::os::fd::RawFd;
+///
+/// use libseccomp::ScmpArch;
+/// use nix::unistd::Pid;
+/// use subuidless::mem::MaybeRemote;
+///
+/// fn main() -> anyhow::Result<()> {
+///     // `AT_FDCWD` passed through an `int` has the upper 32 bits cleared
+///     let at_fdcwd = MaybeRemote::new(Pid::this(), ScmpArch::X8664, 0xffff_ff9c, -1, 0);
+///     assert_eq!(Option::<RawFd>::try_from(at_fdcwd)?, None);
+///     let fd = MaybeRemote::new(Pid::this(), ScmpArch::X86, 3, -1, 0);
+///     assert_eq!(Option::<RawFd>::try_from(fd)?, Some(3));
+///     let negative = MaybeRemote::new(Pid::this(), ScmpArch::X8664, u64::MAX, -1, 0);
+///     assert!(Option::<RawFd>::try_from(negative).is_err());
+///     Ok(())
+/// }
+/// ```
+impl TryFrom<MaybeRemote> for Option<RawFd> {
+    type Error = crate::Error;
+
+    fn try_from(value: MaybeRemote) -> Result<Self, Self::Error> {
+        Self::from_fd(value)
+    }
+}
+
 /// Represents a String living in the memory of another Process  
 /// When converting to a String with `String::try_from()` the remote Process memory is being read.  
+/// The bytes are taken as they are, paths do not need to be valid UTF-8.  
 /// To mitigate TOCTOU style attacks `notify_id_valid` is used *after* the remote memory is read <https://wiki.sei.cmu.edu/confluence/display/c/FIO45-C.+Avoid+TOCTOU+race+conditions+while+accessing+files>
 impl TryFrom<MaybeRemote> for PathBuf {
     type Error = crate::Error;
 
     fn try_from(value: MaybeRemote) -> Result<Self, Self::Error> {
-        let file = File::open(format!("/proc/{}/mem", value.pid)).map_err(attach(Errno::EFAULT))?;
+        let string =
+            value.checked(|data| remote::read_cstring(data.pid, data.pointer, PATH_MAX))?;
 
-        notify_id_valid(value.fd, value.id).map_err(attach(Errno::EPERM))?;
-
-        let mut data = [0; PATH_MAX];
-        file.read_at(&mut data, value.pointer)
-            .map_err(attach(Errno::EFAULT))?;
-        notify_id_valid(value.fd, value.id).map_err(attach(Errno::EPERM))?;
+        Ok(PathBuf::from(OsString::from_vec(string)))
+    }
+}
 
-        // Get null terminator
-        let index_null = data
-            .iter()
-            .position(|&x| x == 0)
-            .context("Could not find the null terminator")
-            .map_err(attach(Errno::ENAMETOOLONG))?;
+/// The name of an xAttribute, a string in the memory of the caller like a path.
+/// Names longer than `XATTR_NAME_MAX` fail with `ERANGE` as they do in the kernel.
+impl TryFrom<MaybeRemote> for OsString {
+    type Error = crate::Error;
 
-        let string = data
-            .get(..index_null)
-            .context("index_null somehow greater than data slice")
-            .map_err(attach(Errno::ENAMETOOLONG))?
-            .to_vec();
+    fn try_from(value: MaybeRemote) -> Result<Self, Self::Error> {
+        let limit = XATTR_NAME_MAX.saturating_add(1);
+        let string = value
+            .checked(|data| remote::read_cstring(data.pid, data.pointer, limit))
+            .map_err(|err| {
+                if let crate::Error::Anyhow(Errno::ENAMETOOLONG, source) = err {
+                    crate::Error::Anyhow(Errno::ERANGE, source)
+                } else {
+                    err
+                }
+            })?;
 
-        Ok(PathBuf::from(
-            String::from_utf8(string).map_err(attach(Errno::ENOENT))?,
-        ))
+        Ok(OsString::from_vec(string))
     }
 }
 
@@ -147,28 +344,352 @@ pub struct RemoteStruct<T: Plain> {
 }
 
 impl<T: Plain> RemoteStruct<T> {
+    /// Reads the struct from the Callers memory
+    pub fn read(&self) -> Result<T, crate::Error> {
+        let mut mem = zeroed::<T>();
+        self.data
+            .checked(|data| remote::read(data.pid, data.pointer, as_bytes_mut(&mut mem)))?;
+        Ok(mem)
+    }
+
+    /// Writes `mem` to the Callers memory
     #[allow(clippy::needless_pass_by_value)] // We want to drop T after writing it
-    pub(crate) fn write(self, mem: T) -> Result<(), crate::Error> {
-        #[allow(
-            unsafe_code,
-            clippy::as_conversions,
-            clippy::ptr_as_ptr,
-            clippy::borrow_as_ptr
-        )]
-        // SAFETY:
-        // Safe only if all the Safety requirements of the Trait are respected
-        let mem = unsafe { slice::from_raw_parts(&mem as *const T as *const u8, size_of::<T>()) };
-
-        let file = OpenOptions::new()
-            .write(true)
-            .open(format!("/proc/{}/mem", self.data.pid))
-            .map_err(attach(Errno::EFAULT))?;
-        notify_id_valid(self.data.fd, self.data.id).map_err(attach(Errno::EPERM))?;
-        file.write_at(mem, self.data.pointer)
-            .map_err(attach(Errno::EFAULT))?;
-        notify_id_valid(self.data.fd, self.data.id).map_err(attach(Errno::EPERM))?;
-
-        Ok(())
+    pub fn write(self, mem: T) -> Result<(), crate::Error> {
+        self.data
+            .checked(|data| remote::write(data.pid, data.pointer, as_bytes(&mem)))
+    }
+
+    /// Treats the pointer as pointing to a `U`, e.g. the struct layout of another ABI
+    #[must_use]
+    pub fn cast<U: Plain>(self) -> RemoteStruct<U> {
+        RemoteStruct {
+            data: self.data,
+            remote_type: PhantomData,
+        }
+    }
+
+    /// Reads the struct, lets `modify` change it and writes it back
+    pub fn modify<F: FnOnce(&mut T)>(self, modify: F) -> Result<(), crate::Error> {
+        let mut mem = self.read()?;
+        modify(&mut mem);
+        self.write(mem)
+    }
+}
+
+impl TryFrom<MaybeRemote> for RemoteBuf {
+    type Error = crate::Error;
+
+    fn try_from(value: MaybeRemote) -> Result<Self, Self::Error> {
+        Ok(RemoteBuf {
+            data: value,
+            len: 0,
+        })
+    }
+}
+
+/// Represents a byte buffer in the Callers memory, like the `value` of `getxattr`  
+/// The size is usually passed in another argument and has to be set with `RemoteBuf::with_len`
+pub struct RemoteBuf {
+    data: MaybeRemote,
+    len: usize,
+}
+
+impl RemoteBuf {
+    /// Sets the size of the buffer in bytes
+    #[must_use]
+    pub fn with_len(self, len: usize) -> Self {
+        Self { len, ..self }
+    }
+
+    /// Size of the buffer in bytes
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the length is zero, as used by syscalls to query the required length
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Reads the whole buffer
+    pub fn read(&self) -> Result<Vec<u8>, crate::Error> {
+        let mut mem = vec![0; self.len];
+        self.data
+            .checked(|data| remote::read(data.pid, data.pointer, &mut mem))?;
+        Ok(mem)
+    }
+
+    /// Writes `mem` to the start of the buffer, fails with `ERANGE` if it does not fit
+    pub fn write(&self, mem: &[u8]) -> Result<(), crate::Error> {
+        if mem.len() > self.len {
+            return Err(Errno::ERANGE.into());
+        }
+        self.data
+            .checked(|data| remote::write(data.pid, data.pointer, mem))
+    }
+
+    /// Writes as much of `mem` as fits, like `getsockopt` truncates options, and returns the written size
+    pub fn write_truncated<T: Plain>(&self, mem: &T) -> Result<usize, crate::Error> {
+        let bytes = as_bytes(mem);
+        let bytes = bytes.get(..self.len).unwrap_or(bytes);
+        self.write(bytes)?;
+        Ok(bytes.len())
+    }
+}
+
+impl TryFrom<MaybeRemote> for RemoteMsghdr {
+    type Error = crate::Error;
+
+    fn try_from(value: MaybeRemote) -> Result<Self, Self::Error> {
+        Ok(RemoteMsghdr { data: value })
+    }
+}
+
+/// The fields of a `struct msghdr`, independent of the layout of the ABI
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::exhaustive_structs)]
+pub struct Msghdr {
+    /// Address of the buffer for the socket address
+    pub name: u64,
+    /// Size of the socket address
+    pub namelen: u32,
+    /// Address of the `struct iovec` array
+    pub iov: u64,
+    /// Number of elements of the `struct iovec` array
+    pub iovlen: usize,
+    /// Address of the buffer for control messages
+    pub control: u64,
+    /// Size of the control messages
+    pub controllen: usize,
+    /// Flags of a received message
+    pub flags: i32,
+}
+
+/// Represents a `struct msghdr` in the Callers memory, like the `msg` of `sendmsg` and `recvmsg`  
+/// The layout of the struct, of its `struct iovec` array and of the control messages depends on the ABI of the Caller.
+pub struct RemoteMsghdr {
+    data: MaybeRemote,
+}
+
+impl RemoteMsghdr {
+    /// Size of pointers and `size_t` in the ABI of the Caller, the alignment of control messages
+    #[must_use]
+    pub fn word_size(&self) -> usize {
+        if is_32bit(self.data.arch) {
+            4
+        } else {
+            8
+        }
+    }
+
+    /// Reads the struct
+    pub fn read(&self) -> Result<Msghdr, crate::Error> {
+        let size = |len: u64| usize::try_from(len).map_err(|_err| Errno::EINVAL);
+        if is_32bit(self.data.arch) {
+            let msghdr = self.remote::<Msghdr32>().read()?;
+            return Ok(Msghdr {
+                name: u64::from(msghdr.msg_name),
+                namelen: msghdr.msg_namelen,
+                iov: u64::from(msghdr.msg_iov),
+                iovlen: size(u64::from(msghdr.msg_iovlen))?,
+                control: u64::from(msghdr.msg_control),
+                controllen: size(u64::from(msghdr.msg_controllen))?,
+                flags: msghdr.msg_flags,
+            });
+        }
+        let msghdr = self.remote::<Msghdr64>().read()?;
+        Ok(Msghdr {
+            name: msghdr.msg_name,
+            namelen: msghdr.msg_namelen,
+            iov: msghdr.msg_iov,
+            iovlen: size(msghdr.msg_iovlen)?,
+            control: msghdr.msg_control,
+            controllen: size(msghdr.msg_controllen)?,
+            flags: msghdr.msg_flags,
+        })
+    }
+
+    /// Writes the fields `recvmsg` returns: the sizes of the socket address and of the control messages and the flags
+    pub fn write_received(&self, msghdr: &Msghdr) -> Result<(), crate::Error> {
+        if is_32bit(self.data.arch) {
+            let controllen = u32::try_from(msghdr.controllen).map_err(|_err| Errno::EINVAL)?;
+            return self.remote::<Msghdr32>().modify(|remote| {
+                remote.msg_namelen = msghdr.namelen;
+                remote.msg_controllen = controllen;
+                remote.msg_flags = msghdr.flags;
+            });
+        }
+        let controllen = u64::try_from(msghdr.controllen).map_err(|_err| Errno::EINVAL)?;
+        self.remote::<Msghdr64>().modify(|remote| {
+            remote.msg_namelen = msghdr.namelen;
+            remote.msg_controllen = controllen;
+            remote.msg_flags = msghdr.flags;
+        })
+    }
+
+    /// The buffer for the socket address
+    #[must_use]
+    pub fn name(&self, msghdr: &Msghdr) -> RemoteBuf {
+        RemoteBuf {
+            data: self.at(msghdr.name),
+            len: usize::try_from(msghdr.namelen).unwrap_or_default(),
+        }
+    }
+
+    /// The buffer for control messages
+    #[must_use]
+    pub fn control(&self, msghdr: &Msghdr) -> RemoteBuf {
+        RemoteBuf {
+            data: self.at(msghdr.control),
+            len: msghdr.controllen,
+        }
+    }
+
+    /// Reads the `struct iovec` array and returns its buffers
+    pub fn iov(&self, msghdr: &Msghdr) -> Result<Vec<RemoteBuf>, crate::Error> {
+        let buf = |base: u64, len: u64| -> Result<RemoteBuf, crate::Error> {
+            Ok(RemoteBuf {
+                data: self.at(base),
+                len: usize::try_from(len).map_err(|_err| Errno::EINVAL)?,
+            })
+        };
+        if is_32bit(self.data.arch) {
+            let iov = RemoteSlice::<Iovec32> {
+                data: self.at(msghdr.iov),
+                len: msghdr.iovlen,
+                remote_type: PhantomData,
+            };
+            return iov
+                .read()?
+                .into_iter()
+                .map(|iovec| buf(u64::from(iovec.iov_base), u64::from(iovec.iov_len)))
+                .collect();
+        }
+        let iov = RemoteSlice::<Iovec64> {
+            data: self.at(msghdr.iov),
+            len: msghdr.iovlen,
+            remote_type: PhantomData,
+        };
+        iov.read()?
+            .into_iter()
+            .map(|iovec| buf(iovec.iov_base, iovec.iov_len))
+            .collect()
+    }
+
+    /// The struct in the layout `T`
+    fn remote<T: Plain>(&self) -> RemoteStruct<T> {
+        RemoteStruct {
+            data: self.data,
+            remote_type: PhantomData,
+        }
+    }
+
+    /// Another pointer into the memory of the Caller
+    fn at(&self, pointer: u64) -> MaybeRemote {
+        MaybeRemote {
+            pointer,
+            ..self.data
+        }
+    }
+}
+
+impl<T: Plain> TryFrom<MaybeRemote> for RemoteSlice<T> {
+    type Error = crate::Error;
+
+    fn try_from(value: MaybeRemote) -> Result<Self, Self::Error> {
+        Ok(RemoteSlice {
+            data: value,
+            len: 0,
+            remote_type: PhantomData,
+        })
+    }
+}
+
+/// Represents an array of `T` in the Callers memory, like the `list` of `getgroups`  
+/// The number of elements is usually passed in another argument and has to be set with `RemoteSlice::with_len`
+pub struct RemoteSlice<T: Plain> {
+    data: MaybeRemote,
+    len: usize,
+    remote_type: PhantomData<T>,
+}
+
+impl<T: Plain> RemoteSlice<T> {
+    /// Sets the number of elements
+    #[must_use]
+    pub fn with_len(self, len: usize) -> Self {
+        Self { len, ..self }
+    }
+
+    /// Number of elements
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the length is zero, as used by syscalls to query the required length
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Reads all elements
+    pub fn read(&self) -> Result<Vec<T>, crate::Error> {
+        let mut mem: Vec<T> = repeat_with(zeroed).take(self.len).collect();
+        self.data
+            .checked(|data| remote::read(data.pid, data.pointer, slice_as_bytes_mut(&mut mem)))?;
+        Ok(mem)
+    }
+
+    /// Writes `mem` to the start of the array, fails with `EINVAL` if it does not fit
+    pub fn write(&self, mem: &[T]) -> Result<(), crate::Error> {
+        if mem.len() > self.len {
+            return Err(Errno::EINVAL.into());
+        }
+        self.data
+            .checked(|data| remote::write(data.pid, data.pointer, slice_as_bytes(mem)))
+    }
+}
+
+/// A `Plain` value with all bytes set to zero
+#[must_use]
+pub fn zeroed<T: Plain>() -> T {
+    #[allow(unsafe_code)]
+    // SAFETY:
+    // Every bit pattern is a valid `Plain` value, including all zeroes
+    unsafe {
+        MaybeUninit::zeroed().assume_init()
+    }
+}
+
+/// The bytes of a `Plain` value
+pub(crate) fn as_bytes<T: Plain>(mem: &T) -> &[u8] {
+    slice_as_bytes(slice::from_ref(mem))
+}
+
+/// The bytes of a `Plain` value, every change results in a valid value
+pub(crate) fn as_bytes_mut<T: Plain>(mem: &mut T) -> &mut [u8] {
+    slice_as_bytes_mut(slice::from_mut(mem))
+}
+
+/// The bytes of `Plain` values
+fn slice_as_bytes<T: Plain>(mem: &[T]) -> &[u8] {
+    #[allow(unsafe_code)]
+    // SAFETY:
+    // Safe only if all the Safety requirements of the Trait are respected
+    unsafe {
+        slice::from_raw_parts(mem.as_ptr().cast::<u8>(), size_of_val(mem))
+    }
+}
+
+/// The bytes of `Plain` values, every change results in valid values
+fn slice_as_bytes_mut<T: Plain>(mem: &mut [T]) -> &mut [u8] {
+    #[allow(unsafe_code)]
+    // SAFETY:
+    // Safe only if all the Safety requirements of the Trait are respected
+    unsafe {
+        slice::from_raw_parts_mut(mem.as_mut_ptr().cast::<u8>(), size_of_val(mem))
     }
 }
 
@@ -180,6 +701,7 @@ impl<T: Plain> RemoteStruct<T> {
 /// See safety of `slice::from_raw_parts` and <https://doc.rust-lang.org/nomicon/transmutes.html> and <https://wiki.sei.cmu.edu/confluence/display/c/DCL39-C.+Avoid+information+leakage+when+passing+a+structure+across+a+trust+boundary>
 /// * `Self` must not contain any Form of padding
 /// * `Self` must be `#[repr(C)]`
+/// * Every bit pattern must be a valid `Self`, as it may be read from another Process
 /// * If written to another Process, they must share the same Architecture
 pub unsafe trait Plain: Sized {}
 
@@ -188,3 +710,40 @@ pub unsafe trait Plain: Sized {}
 /// `libc::stat` is a `libc` `repr(C)` struct that contain no Padding and is even written from C code.
 /// Because there is no Constructor and the padding is private there should be no situation where it would be possible to create `stat` in safe Rust.
 unsafe impl Plain for stat {}
+
+#[allow(unsafe_code)]
+/// SAFETY:
+/// `libc::ucred` is a `repr(C)` struct of three 4-byte integers, which leaves no padding.
+unsafe impl Plain for ucred {}
+
+#[allow(unsafe_code)]
+/// SAFETY:
+/// `libc::shmid_ds` is a `repr(C)` struct whose `ipc_perm` and other fields, including the reserved ones, leave no padding.
+unsafe impl Plain for shmid_ds {}
+
+#[allow(unsafe_code)]
+/// SAFETY:
+/// See `shmid_ds`
+unsafe impl Plain for semid_ds {}
+
+#[allow(unsafe_code)]
+/// SAFETY:
+/// See `shmid_ds`
+unsafe impl Plain for msqid_ds {}
+
+#[allow(unsafe_code)]
+/// SAFETY:
+/// Integers have no padding and every bit pattern is a valid integer.
+unsafe impl Plain for u8 {}
+#[allow(unsafe_code)]
+/// SAFETY:
+/// See `u8`
+unsafe impl Plain for u32 {}
+#[allow(unsafe_code)]
+/// SAFETY:
+/// See `u8`
+unsafe impl Plain for i32 {}
+#[allow(unsafe_code)]
+/// SAFETY:
+/// See `u8`
+unsafe impl Plain for u64 {}
@@ -0,0 +1,137 @@
+//! Runs operations in a child process that joined namespaces of a calling process
+//!
+//! Some objects are only reachable from inside of the namespaces of the caller, like System V IPC objects,
+//! and the kernel only accepts some arguments from processes inside of them, like the pid of `SCM_CREDENTIALS`.
+//! The child joins the user namespace of the caller first, which grants it the capabilities for the other namespaces.
+use std::fs::{metadata, File};
+use std::io::Read;
+use std::os::fd::OwnedFd;
+use std::os::unix::fs::MetadataExt;
+
+use anyhow::Context;
+use nix::errno::Errno;
+use nix::fcntl::OFlag;
+use nix::libc::{self, c_int, c_long};
+use nix::sched::{setns, CloneFlags};
+use nix::sys::wait::waitpid;
+use nix::unistd::{fork, pipe2, write, ForkResult};
+
+use crate::error::attach;
+use crate::mem::{as_bytes, as_bytes_mut, Plain};
+
+/// Runs `operation` on `value` in a child process that joined the user namespace and `namespaces` of process `pid`,
+/// which may be `CLONE_NEWIPC` and `CLONE_NEWPID`.
+/// Returns the result of `operation`, or the errno it failed with, together with `value` as it left it.
+/// `operation` runs in a child of a multithreaded process, so it may only issue syscalls.
+pub fn in_namespace<T: Plain, F: FnOnce(&mut T) -> c_long>(
+    pid: u32,
+    namespaces: CloneFlags,
+    mut value: T,
+    operation: F,
+) -> Result<(Result<c_long, Errno>, T), crate::Error> {
+    let user = namespace_file(pid, "user")?;
+    let ipc = if namespaces.contains(CloneFlags::CLONE_NEWIPC) {
+        namespace_file(pid, "ipc")?
+    } else {
+        None
+    };
+    let pid_namespace = if namespaces.contains(CloneFlags::CLONE_NEWPID) {
+        namespace_file(pid, "pid")?
+    } else {
+        None
+    };
+    let new_pid = pid_namespace.is_some();
+    let (reader, writer) = pipe2(OFlag::O_CLOEXEC)?;
+
+    #[allow(unsafe_code)]
+    // SAFETY:
+    // The child only issues syscalls until it exits with `_exit(2)`, it neither allocates nor takes locks
+    let child = match unsafe { fork() }? {
+        ForkResult::Parent { child } => child,
+        ForkResult::Child => {
+            let joined = user
+                .map_or(Ok(()), |user| setns(user, CloneFlags::CLONE_NEWUSER))
+                .and_then(|()| ipc.map_or(Ok(()), |ipc| setns(ipc, CloneFlags::CLONE_NEWIPC)))
+                .and_then(|()| {
+                    pid_namespace.map_or(Ok(()), |pid_namespace| {
+                        setns(pid_namespace, CloneFlags::CLONE_NEWPID)
+                    })
+                });
+            // A child that could not join sends nothing
+            if joined.is_ok() {
+                if new_pid {
+                    // Only the children of the child are part of the joined PID namespace
+                    #[allow(unsafe_code)]
+                    // SAFETY:
+                    // See the first `fork`, the child is single threaded
+                    match unsafe { fork() } {
+                        Ok(ForkResult::Parent { child }) => {
+                            let _status = waitpid(child, None);
+                        }
+                        Ok(ForkResult::Child) => run(&writer, &mut value, operation),
+                        Err(_err) => {}
+                    }
+                } else {
+                    run(&writer, &mut value, operation);
+                }
+            }
+            exit()
+        }
+    };
+    drop(writer);
+
+    let mut result = [0_u8; 8];
+    let mut reader = File::from(reader);
+    let received = reader
+        .read_exact(&mut result)
+        .and_then(|()| reader.read_exact(as_bytes_mut(&mut value)));
+    waitpid(child, None)?;
+    received
+        .context("Could not join the namespaces of the process")
+        .map_err(attach(Errno::EPERM))?;
+
+    #[allow(clippy::host_endian_bytes)] // Written by the child
+    let result = c_long::from_ne_bytes(result);
+    if result < 0 {
+        let errno = c_int::try_from(result.saturating_neg()).unwrap_or_default();
+        return Ok((Err(Errno::from_raw(errno)), value));
+    }
+    Ok((Ok(result), value))
+}
+
+/// Runs `operation` on `value` in the child and sends its result and `value` through `writer`, then exits
+fn run<T: Plain, F: FnOnce(&mut T) -> c_long>(writer: &OwnedFd, value: &mut T, operation: F) -> ! {
+    let result = match operation(value) {
+        -1 => c_long::from(Errno::last_raw()).saturating_neg(),
+        result => result,
+    };
+    #[allow(clippy::host_endian_bytes)] // Only read by the parent
+    let _result = write(writer, &result.to_ne_bytes()).and_then(|_| write(writer, as_bytes(value)));
+    exit()
+}
+
+/// Ends the child
+fn exit() -> ! {
+    #[allow(unsafe_code)]
+    // SAFETY:
+    // `_exit(2)` skips the exit handlers of the parent
+    unsafe {
+        libc::_exit(0_i32)
+    }
+}
+
+/// Opens the namespace `kind` of process `pid`, `None` if the daemon is already part of it
+fn namespace_file(pid: u32, kind: &str) -> Result<Option<File>, crate::Error> {
+    let path = format!("/proc/{pid}/ns/{kind}");
+    let own = metadata(format!("/proc/self/ns/{kind}"))
+        .context("Could not inspect the namespace of the daemon")
+        .map_err(attach(Errno::EPERM))?;
+    let file = File::open(path)
+        .context("Could not open the namespace of the process")
+        .map_err(attach(Errno::ESRCH))?;
+    let target = file
+        .metadata()
+        .context("Could not inspect the namespace of the process")
+        .map_err(attach(Errno::ESRCH))?;
+    Ok((target.dev() != own.dev() || target.ino() != own.ino()).then_some(file))
+}
@@ -0,0 +1,118 @@
+//! Translation of host pids into the PID namespace of a container
+//!
+//! The worker receives notifications with the pids of the PID namespace of the daemon.
+//! The `NSpid` field of `/proc/<pid>/status` lists the pid of a process in every namespace
+//! from the one of the reading `/proc` down to its own, which tells apart the processes of the container
+//! from the ones outside of it and allows translating between the two.
+//! The depth of a namespace alone does not tell apart sibling namespaces,
+//! so the namespace at that depth is compared by the device and inode of `/proc/<pid>/ns/pid` as well.
+use std::fs::File;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+use anyhow::{ensure, Context, Result};
+use nix::errno::Errno;
+use nix::sys::stat::fstat;
+use procfs::process::Status;
+use procfs::FromRead;
+use rustix::process::Pid;
+
+use crate::error::attach;
+
+#[allow(unsafe_code)]
+mod ioctl {
+    use nix::ioctl_none;
+
+    ioctl_none!(
+        /// `NS_GET_PARENT`, see `ioctl_ns(2)`
+        ns_get_parent,
+        0xb7,
+        0x2
+    );
+}
+
+/// A PID namespace, identified by the device and inode of `/proc/<pid>/ns/pid`
+pub type Namespace = (u64, u64);
+
+/// The PID namespace of a container as seen from the host `/proc`
+#[derive(Debug)]
+pub struct PidNamespace {
+    /// Number of PID namespaces between the daemon and the container, including the one of the container
+    level: usize,
+    /// The PID namespace of the container
+    namespace: Namespace,
+}
+
+impl PidNamespace {
+    /// Determines the PID namespace of the container process `init`
+    pub fn new(init: Pid) -> Result<Self> {
+        let init = init.as_raw_nonzero().get();
+        Ok(Self {
+            level: nspid(init)?.len(),
+            namespace: namespace(u32::try_from(init)?, 0)?,
+        })
+    }
+
+    /// Translates the host `pid` into the PID namespace of the container.
+    /// Processes of nested PID namespaces are translated as well.
+    /// Returns `None` if the process is not part of the container, e.g. the runtime setting it up.
+    pub fn translate(&self, pid: i32) -> Result<Option<i32>> {
+        let nspid = nspid(pid)?;
+        let Some(up) = nspid.len().checked_sub(self.level) else {
+            return Ok(None);
+        };
+        // A sibling of the namespace of the container has the same depth
+        if namespace(u32::try_from(pid)?, up)? != self.namespace {
+            return Ok(None);
+        }
+        Ok(self
+            .level
+            .checked_sub(1)
+            .and_then(|index| nspid.get(index))
+            .copied())
+    }
+}
+
+/// The PID namespace `up` levels above the one of process `pid`
+pub fn namespace(pid: u32, up: usize) -> Result<Namespace, crate::Error> {
+    let mut namespace = OwnedFd::from(
+        File::open(format!("/proc/{pid}/ns/pid"))
+            .context("Could not open the PID namespace of the process")
+            .map_err(attach(Errno::ESRCH))?,
+    );
+    for _ in 0..up {
+        #[allow(unsafe_code)]
+        // SAFETY:
+        // `NS_GET_PARENT` takes no argument
+        let parent = unsafe { ioctl::ns_get_parent(namespace.as_raw_fd()) }?;
+        #[allow(unsafe_code)]
+        // SAFETY:
+        // The kernel opened a new descriptor of the parent namespace, nothing else owns it
+        let parent = unsafe { OwnedFd::from_raw_fd(parent) };
+        namespace = parent;
+    }
+    let stat = fstat(namespace.as_raw_fd())?;
+    Ok((stat.st_dev, stat.st_ino))
+}
+
+/// Reads `/proc/<pid>/status`, its pids and ids are the ones of the namespaces of the daemon
+pub fn status(pid: u32) -> Result<Status, crate::Error> {
+    let status = File::open(format!("/proc/{pid}/status"))
+        .context("Could not open the status of the process")
+        .map_err(attach(Errno::ESRCH))?;
+    Status::from_read(status)
+        .context("Could not parse the status of the process")
+        .map_err(attach(Errno::ESRCH))
+}
+
+/// Reads the pids of `pid` in all the namespaces it is part of, starting with the one of the daemon
+fn nspid(pid: i32) -> Result<Vec<i32>> {
+    let nspid = status(u32::try_from(pid)?)
+        .with_context(|| format!("Could not read the status of process {pid}"))?
+        .nspid
+        .context("Kernel does not report NSpid")?;
+    ensure!(
+        nspid.first() == Some(&pid),
+        "Process {pid} is not in the PID namespace of the daemon"
+    );
+    Ok(nspid)
+}
@@ -0,0 +1,104 @@
+//! Bounded pool of threads that run the syscall handlers
+//!
+//! A slow handler, e.g. a `setxattr` on a network filesystem, only occupies one thread,
+//! the notifications of the other processes of the container are handled in the meantime.
+use std::num::NonZeroUsize;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::{Builder, JoinHandle};
+
+use anyhow::{Context, Result};
+use log::warn;
+
+/// Work submitted to the `Pool`
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Fixed number of threads executing jobs in the order they were submitted.
+/// At most one job per thread is queued, `Pool::execute` blocks once the queue is full.
+/// Dropping the `Pool` waits until all submitted jobs are done.
+///
+/// # Examples
+/// ```
+/// use std::num::NonZeroUsize;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+/// use subuidless::pool::Pool;
+///
+/// fn main() -> anyhow::Result<()> {
+///     let counter = Arc::new(AtomicUsize::new(0));
+///     let pool = Pool::new(NonZeroUsize::new(4).unwrap())?;
+///     for _ in 0..16 {
+///         let counter = Arc::clone(&counter);
+///         pool.execute(move || {
+///             counter.fetch_add(1, Ordering::Relaxed);
+///         })?;
+///     }
+///     drop(pool);
+///     assert_eq!(counter.load(Ordering::Relaxed), 16);
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Pool {
+    sender: Option<SyncSender<Job>>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl Pool {
+    /// Spawns `size` threads
+    pub fn new(size: NonZeroUsize) -> Result<Self> {
+        let (sender, receiver) = sync_channel::<Job>(size.get());
+        let receiver = Arc::new(Mutex::new(receiver));
+        let threads = (0..size.get())
+            .map(|index| {
+                let receiver = Arc::clone(&receiver);
+                Builder::new()
+                    .name(format!("handler-{index}"))
+                    .spawn(move || work(&receiver))
+            })
+            .collect::<Result<_, _>>()
+            .context("Could not spawn a handler thread")?;
+
+        Ok(Self {
+            sender: Some(sender),
+            threads,
+        })
+    }
+
+    /// Queues `job` to run on the next free thread
+    pub fn execute<F: FnOnce() + Send + 'static>(&self, job: F) -> Result<()> {
+        self.sender
+            .as_ref()
+            .context("Pool is shut down")?
+            .send(Box::new(job))
+            .ok()
+            .context("All handler threads exited")
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        // Closing the channel stops the threads once the queue is empty
+        drop(self.sender.take());
+        for thread in self.threads.drain(..) {
+            if thread.join().is_err() {
+                warn!("A handler thread panicked");
+            }
+        }
+    }
+}
+
+/// Runs jobs until the `Pool` is dropped
+fn work(receiver: &Mutex<Receiver<Job>>) {
+    loop {
+        // The lock is released before the job runs, so the other threads can take the next one
+        let job = receiver
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .recv();
+        let Ok(job) = job else {
+            return;
+        };
+        job();
+    }
+}
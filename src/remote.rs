@@ -0,0 +1,140 @@
+//! Access to the memory of the process that issued a syscall
+//!
+//! `process_vm_readv(2)` and `process_vm_writev(2)` are used where permitted, otherwise `/proc/<pid>/mem`,
+//! whose file descriptors are cached per pid. Strings are read page by page, so a string that ends right before
+//! an unmapped page can still be read.
+use std::cmp::min;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, IoSlice, IoSliceMut};
+use std::os::unix::fs::FileExt;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+
+use anyhow::anyhow;
+use nix::errno::Errno;
+use nix::sys::uio::{process_vm_readv, process_vm_writev, RemoteIoVec};
+use nix::unistd::Pid;
+use rustix::param::page_size;
+
+use crate::error::attach;
+
+/// Number of cached `/proc/<pid>/mem` files after which the cache is cleared
+const MAX_MEM_FILES: usize = 64;
+
+/// Open `/proc/<pid>/mem` files, used if `process_vm_readv` is not permitted
+static MEM_FILES: OnceLock<Mutex<HashMap<Pid, Arc<File>>>> = OnceLock::new();
+
+/// Reads `buf.len()` bytes at `address` of process `pid`
+pub fn read(pid: Pid, address: u64, buf: &mut [u8]) -> Result<(), crate::Error> {
+    let mut done = 0;
+    while let Some(rest) = buf.get_mut(done..).filter(|rest| !rest.is_empty()) {
+        let address = address
+            .checked_add(u64::try_from(done).map_err(attach(Errno::EFAULT))?)
+            .ok_or(Errno::EFAULT)?;
+        match read_chunk(pid, address, rest)? {
+            0 => return Err(Errno::EFAULT.into()),
+            read => done = done.saturating_add(read),
+        }
+    }
+    Ok(())
+}
+
+/// Reads the NUL terminated string at `address` of process `pid`, without the NUL.
+/// Fails with `ENAMETOOLONG` if there is no NUL within the first `limit` bytes.
+pub fn read_cstring(pid: Pid, mut address: u64, limit: usize) -> Result<Vec<u8>, crate::Error> {
+    let page_size = u64::try_from(page_size()).map_err(attach(Errno::EFAULT))?;
+    let mut string = Vec::new();
+
+    while string.len() < limit {
+        // Stop at the end of the page, the next one might not be mapped
+        let page_rest = page_size
+            .checked_sub(address.checked_rem(page_size).unwrap_or_default())
+            .unwrap_or(page_size);
+        let len = min(
+            usize::try_from(page_rest).map_err(attach(Errno::EFAULT))?,
+            limit.saturating_sub(string.len()),
+        );
+        let mut chunk = vec![0; len];
+        read(pid, address, &mut chunk)?;
+
+        if let Some(nul) = chunk.iter().position(|&byte| byte == 0) {
+            chunk.truncate(nul);
+            string.append(&mut chunk);
+            return Ok(string);
+        }
+        string.append(&mut chunk);
+        address = address.checked_add(page_rest).ok_or(Errno::EFAULT)?;
+    }
+
+    Err(attach(Errno::ENAMETOOLONG)(anyhow!(
+        "String is longer than {limit} bytes"
+    )))
+}
+
+/// Writes `data` to `address` of process `pid`
+pub fn write(pid: Pid, address: u64, data: &[u8]) -> Result<(), crate::Error> {
+    let remote = [RemoteIoVec {
+        base: usize::try_from(address).map_err(attach(Errno::EFAULT))?,
+        len: data.len(),
+    }];
+    match process_vm_writev(pid, &[IoSlice::new(data)], &remote) {
+        Ok(written) if written == data.len() => Ok(()),
+        // Like the kernel, memory that is not mapped writable by the process is not written
+        Ok(_) => Err(Errno::EFAULT.into()),
+        Err(Errno::EPERM | Errno::ENOSYS) => {
+            with_mem_file(pid, |file| file.write_all_at(data, address))
+                .map_err(attach(Errno::EFAULT))
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Reads at most up to the end of the mapping at `address`, returns the number of bytes read
+fn read_chunk(pid: Pid, address: u64, buf: &mut [u8]) -> Result<usize, crate::Error> {
+    let remote = [RemoteIoVec {
+        base: usize::try_from(address).map_err(attach(Errno::EFAULT))?,
+        len: buf.len(),
+    }];
+    match process_vm_readv(pid, &mut [IoSliceMut::new(buf)], &remote) {
+        Err(Errno::EPERM | Errno::ENOSYS) => with_mem_file(pid, |file| {
+            // The file of a process that exited or ran `execve(2)` reads nothing
+            match file.read_at(buf, address)? {
+                0 => Err(io::ErrorKind::UnexpectedEof.into()),
+                read => Ok(read),
+            }
+        })
+        .map_err(attach(Errno::EFAULT)),
+        result => Ok(result?),
+    }
+}
+
+/// Runs `access` with the cached `/proc/<pid>/mem` file.
+/// The file is reopened once if it fails, as it might belong to an earlier process with the same pid or the image before an `execve(2)`.
+fn with_mem_file<T>(pid: Pid, mut access: impl FnMut(&File) -> io::Result<T>) -> io::Result<T> {
+    let files = MEM_FILES.get_or_init(Mutex::default);
+    let cached = files
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(&pid)
+        .cloned();
+    if let Some(file) = cached {
+        if let Ok(result) = access(&file) {
+            return Ok(result);
+        }
+    }
+
+    let file = Arc::new(
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(format!("/proc/{pid}/mem"))?,
+    );
+    let mut files = files.lock().unwrap_or_else(PoisonError::into_inner);
+    // Files of exited processes are never used again
+    if files.len() >= MAX_MEM_FILES {
+        files.clear();
+    }
+    files.insert(pid, Arc::clone(&file));
+    drop(files);
+    access(&file)
+}
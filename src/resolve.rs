@@ -0,0 +1,132 @@
+//! Resolution of the paths passed to a syscall
+//!
+//! Paths are resolved by the daemon as the calling process would, without joining its mount namespace:
+//! `openat2` with `RESOLVE_IN_ROOT` keeps absolute paths, `..` and symlinks inside `/proc/<pid>/root`.
+//! The result is an `O_PATH` file descriptor, so concurrent handlers never depend on a working directory.
+use std::ffi::OsStr;
+use std::os::fd::{AsFd, AsRawFd, OwnedFd, RawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use libseccomp::notify_id_valid;
+use nix::errno::Errno;
+use nix::fcntl::AtFlags;
+use rustix::fs::{open, openat2, readlinkat, Mode, OFlags, ResolveFlags, CWD};
+use rustix::io as rio;
+
+use crate::error::attach;
+use crate::syscall::Notification;
+
+/// Attempts of a lookup that raced with a rename, as `openat2(2)` recommends retrying them
+const LOOKUP_ATTEMPTS: usize = 16;
+
+/// Opens `path`, as passed by the caller of `notification` together with `dirfd` and `flags`, as an `O_PATH` file descriptor.
+/// Relative paths are resolved against `dirfd` or the working directory of the caller if `dirfd` is `None`.
+/// Symbolic links in the last component are followed unless `flags` contains `AT_SYMLINK_NOFOLLOW`.
+/// An empty `path` refers to `dirfd` itself if `flags` contains `AT_EMPTY_PATH`.
+pub fn resolve(
+    notification: &Notification,
+    dirfd: Option<RawFd>,
+    path: &Path,
+    flags: AtFlags,
+) -> Result<OwnedFd, crate::Error> {
+    let pid = notification.req.pid;
+    // The pid might belong to another process if the caller exited in the meantime
+    let check_valid =
+        || notify_id_valid(notification.fd, notification.req.id).map_err(attach(Errno::EPERM));
+    let mut oflags = OFlags::PATH | OFlags::CLOEXEC;
+    if flags.contains(AtFlags::AT_SYMLINK_NOFOLLOW) {
+        oflags |= OFlags::NOFOLLOW;
+    }
+
+    let base = match dirfd {
+        Some(dirfd) => format!("/proc/{pid}/fd/{dirfd}"),
+        None => format!("/proc/{pid}/cwd"),
+    };
+    // A missing entry in `/proc/<pid>/fd` means `dirfd` is not open in the caller
+    let bad_dirfd = |err: rio::Errno| {
+        if err == rio::Errno::NOENT && dirfd.is_some() {
+            rio::Errno::BADF
+        } else {
+            err
+        }
+    };
+
+    if path.as_os_str().is_empty() {
+        if !flags.contains(AtFlags::AT_EMPTY_PATH) {
+            return Err(Errno::ENOENT.into());
+        }
+        // Opening the magic link follows it to the file the descriptor refers to
+        let file = open(base, OFlags::PATH | OFlags::CLOEXEC, Mode::empty()).map_err(bad_dirfd)?;
+        check_valid()?;
+        return Ok(file);
+    }
+
+    let root_link = format!("/proc/{pid}/root");
+    let root = open(
+        &root_link,
+        OFlags::PATH | OFlags::DIRECTORY | OFlags::CLOEXEC,
+        Mode::empty(),
+    )?;
+    check_valid()?;
+    if path.is_absolute() {
+        return Ok(lookup(&root, path, oflags, ResolveFlags::IN_ROOT)?);
+    }
+
+    let base_fd = open(
+        &base,
+        OFlags::PATH | OFlags::DIRECTORY | OFlags::CLOEXEC,
+        Mode::empty(),
+    )
+    .map_err(bad_dirfd)?;
+    match lookup(&base_fd, path, oflags, ResolveFlags::BENEATH) {
+        Err(rio::Errno::XDEV) => {}
+        result => return Ok(result?),
+    }
+
+    // The path leaves the directory through `..` or an absolute symlink,
+    // so it is resolved again from the root with the path of the directory inside of the root in front.
+    // Both links are read from the mount namespace of the daemon, so the path of the root is a prefix of the one of the directory.
+    let root_path = readlinkat(CWD, &root_link, Vec::new())?;
+    let base_path = readlinkat(CWD, &base, Vec::new())?;
+    check_valid()?;
+    let base_path = Path::new(OsStr::from_bytes(base_path.as_bytes()))
+        .strip_prefix(OsStr::from_bytes(root_path.as_bytes()))
+        .map_err(|_outside| Errno::ENOENT)?;
+    let path = Path::new("/").join(base_path).join(path);
+    Ok(lookup(&root, &path, oflags, ResolveFlags::IN_ROOT)?)
+}
+
+/// `openat2(2)` of `path` below `dirfd`, repeated a few times if it fails with `EAGAIN` because a concurrent rename raced the lookup
+fn lookup<Fd: AsFd>(
+    dirfd: Fd,
+    path: &Path,
+    oflags: OFlags,
+    resolve: ResolveFlags,
+) -> Result<OwnedFd, rio::Errno> {
+    for _ in 1..LOOKUP_ATTEMPTS {
+        match openat2(&dirfd, path, oflags, Mode::empty(), resolve) {
+            Err(rio::Errno::AGAIN) => {}
+            result => return result,
+        }
+    }
+    openat2(&dirfd, path, oflags, Mode::empty(), resolve)
+}
+
+/// Path through which the file behind `fd` can be accessed by functions that only take paths
+///
+/// # Examples
+/// ```
+/// use std::fs::{read_link, File};
+/// use subuidless::resolve::fd_path;
+///
+/// fn main() -> anyhow::Result<()> {
+///     let file = File::open("/")?;
+///     assert_eq!(read_link(fd_path(&file))?.as_os_str(), "/");
+///     Ok(())
+/// }
+/// ```
+#[must_use]
+pub fn fd_path<Fd: AsRawFd>(fd: &Fd) -> PathBuf {
+    PathBuf::from(format!("/proc/self/fd/{}", fd.as_raw_fd()))
+}
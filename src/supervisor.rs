@@ -0,0 +1,165 @@
+//! Supervision of forked worker processes
+//!
+//! Workers are tracked with pidfds, so they are reaped as soon as they exit and signals can be forwarded to them
+//! without racing against pid reuse.
+use std::os::fd::{AsFd, BorrowedFd, OwnedFd};
+
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use nix::sys::signal::{kill, SigSet, Signal};
+use nix::sys::signalfd::{SfdFlags, SignalFd};
+use nix::sys::wait::{waitid, Id, WaitPidFlag, WaitStatus};
+use nix::unistd::{fork, ForkResult, Pid};
+use rustix::event::{poll, PollFd, PollFlags};
+use rustix::process::{pidfd_open, Pid as RawPid, PidfdFlags};
+
+/// Signals that shut the daemon and its workers down
+const SHUTDOWN_SIGNALS: [Signal; 3] = [Signal::SIGTERM, Signal::SIGINT, Signal::SIGHUP];
+
+/// Blocks the shutdown signals and returns a `SignalFd` that receives them instead.
+/// The signal mask is inherited by forked processes, which should call this function again to get their own `SignalFd`.
+pub fn shutdown_signals() -> Result<SignalFd> {
+    let mut mask = SigSet::empty();
+    for signal in SHUTDOWN_SIGNALS {
+        mask.add(signal);
+    }
+    mask.thread_block()
+        .context("Could not block the shutdown signals")?;
+    SignalFd::with_flags(&mask, SfdFlags::SFD_CLOEXEC).context("Could not create a signalfd")
+}
+
+/// Something that happened while waiting in `Supervisor::wait`
+#[derive(Debug)]
+#[allow(clippy::exhaustive_enums)] // Callers are expected to handle every event
+pub enum Event {
+    /// The file descriptor passed to `Supervisor::wait` is readable
+    Ready,
+    /// A shutdown signal was received
+    Shutdown(Signal),
+    /// A worker exited and was reaped
+    Exited(WaitStatus),
+}
+
+/// A forked process
+#[derive(Debug)]
+struct Worker {
+    pid: Pid,
+    pid_fd: OwnedFd,
+}
+
+/// Tracks forked workers and receives the shutdown signals
+#[derive(Debug)]
+pub struct Supervisor {
+    signals: SignalFd,
+    workers: Vec<Worker>,
+}
+
+impl Supervisor {
+    /// Blocks the shutdown signals, they are reported by `Supervisor::wait` from now on
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            signals: shutdown_signals()?,
+            workers: Vec::new(),
+        })
+    }
+
+    /// Number of workers that are still running
+    #[must_use]
+    pub fn workers(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Forks a new worker.
+    /// Returns the pid of the worker in the parent and `None` in the worker.
+    /// The worker should drop its copy of the `Supervisor` right away.
+    pub fn fork(&mut self) -> Result<Option<Pid>> {
+        #[allow(unsafe_code)]
+        // SAFETY:
+        // The supervising process is not multithreaded.
+        match unsafe { fork() }.context("Could not fork a worker")? {
+            ForkResult::Parent { child } => {
+                let raw_pid =
+                    RawPid::from_raw(child.as_raw()).context("Fork returned an invalid pid")?;
+                // The child can not be reaped before we wait for it, so the pid can not be reused in between
+                let pid_fd = pidfd_open(raw_pid, PidfdFlags::empty())
+                    .context("Could not open a pidfd for the worker")?;
+                self.workers.push(Worker { pid: child, pid_fd });
+                Ok(Some(child))
+            }
+            ForkResult::Child => Ok(None),
+        }
+    }
+
+    /// Waits until `fd` becomes readable, a shutdown signal arrives or a worker exits.
+    /// Exited workers are reaped before `Event::Exited` is returned.
+    pub fn wait(&mut self, fd: Option<BorrowedFd<'_>>) -> Result<Event> {
+        loop {
+            let ready: Vec<bool> = {
+                let mut poll_fds = vec![PollFd::new(&self.signals, PollFlags::IN)];
+                poll_fds.extend(
+                    self.workers
+                        .iter()
+                        .map(|worker| PollFd::new(&worker.pid_fd, PollFlags::IN)),
+                );
+                if let Some(fd) = fd.as_ref() {
+                    poll_fds.push(PollFd::new(fd, PollFlags::IN));
+                }
+
+                poll(&mut poll_fds, -1).context("Could not poll")?;
+                poll_fds
+                    .iter()
+                    .map(|poll_fd| !poll_fd.revents().is_empty())
+                    .collect()
+            };
+            let mut ready = ready.into_iter();
+
+            if ready.next().unwrap_or_default() {
+                if let Some(siginfo) = self.signals.read_signal()? {
+                    let signal = Signal::try_from(i32::try_from(siginfo.ssi_signo)?)?;
+                    return Ok(Event::Shutdown(signal));
+                }
+            }
+
+            let exited: Vec<bool> = ready.by_ref().take(self.workers.len()).collect();
+            if let Some(index) = exited.iter().position(|&exited| exited) {
+                let worker = self.workers.swap_remove(index);
+                let status = waitid(Id::PIDFd(worker.pid_fd.as_fd()), WaitPidFlag::WEXITED)
+                    .with_context(|| format!("Could not reap worker {}", worker.pid))?;
+                debug!("Worker {} exited: {status:?}", worker.pid);
+                return Ok(Event::Exited(status));
+            }
+
+            if ready.next().unwrap_or_default() {
+                return Ok(Event::Ready);
+            }
+        }
+    }
+
+    /// Sends `signal` to all workers
+    pub fn signal(&self, signal: Signal) {
+        for worker in &self.workers {
+            if let Err(err) = kill(worker.pid, signal) {
+                warn!("Could not send {signal} to worker {}: {err}", worker.pid);
+            }
+        }
+    }
+
+    /// Stops all workers and waits until they are reaped.
+    /// With `drain` the workers receive `SIGTERM` and may finish their in-flight notifications,
+    /// a second shutdown signal kills them. Without `drain` they are killed right away.
+    pub fn shutdown(mut self, drain: bool) -> Result<()> {
+        self.signal(if drain {
+            Signal::SIGTERM
+        } else {
+            Signal::SIGKILL
+        });
+
+        while !self.workers.is_empty() {
+            if let Event::Shutdown(signal) = self.wait(None)? {
+                warn!("Received {signal} while draining, killing the workers");
+                self.signal(Signal::SIGKILL);
+            }
+        }
+        Ok(())
+    }
+}
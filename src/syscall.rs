@@ -1,80 +1,222 @@
-use libseccomp::{ScmpFd, ScmpNotifReq, ScmpSyscall};
+use std::collections::HashMap;
+use std::ops::Neg;
+use std::os::fd::{AsFd, OwnedFd, RawFd};
 
+use libseccomp::error::SeccompErrno;
+use libseccomp::{
+    notify_id_valid, ScmpArch, ScmpFd, ScmpNotifReq, ScmpNotifResp, ScmpNotifRespFlags,
+    ScmpSyscall, SeccompError,
+};
+use log::{debug, warn};
+use nix::errno::Errno;
+use nix::fcntl::OFlag;
+use nix::libc::EIO;
+use nix::unistd::Pid;
+use rustix::process::{pidfd_getfd, pidfd_open, Pid as RawPid, PidfdFlags, PidfdGetfdFlags};
+
+use crate::abi::{native_archs, supported_archs};
+use crate::addfd::{add_fd, send_fd};
+use crate::config::Config;
+use crate::error::attach;
+use crate::mem::MaybeRemote;
+
+mod capget;
+mod execve;
+mod faccessat;
+mod fchmodat;
 mod fchownat;
 mod fstatat;
+mod fstatat64;
+mod getsockopt;
+mod getuid;
+mod prctl;
+mod sendmsg;
+mod setuid;
+mod setxattr;
+mod shmctl;
 /// Syscall trait for the `inventory` crate
-/// All Implementation of this trait get collected into a `HashMap` where `ScmpSyscall` is the key
+/// All Implementation of this trait get collected into a `HashMap` where `ScmpArch` and `ScmpSyscall` are the key
 /// This allows for `O(n)` access when a new `ScmpNotifReq` is received.
 pub trait Syscall: Sync {
     /// Main function of the syscall. Everything the syscall does, happens here
-    fn execute(&self, req: ScmpNotifReq, fd: ScmpFd) -> Result<i64, crate::Error>;
+    fn execute(&self, notification: Notification) -> Result<Outcome, crate::Error>;
+
+    /// Get the associated `ScmpSyscall` of `arch` - used to build the `HashMap`
+    fn get_syscall(&self, arch: ScmpArch) -> anyhow::Result<ScmpSyscall>;
+
+    /// Get the architectures whose ABI the implementation handles
+    fn get_archs(&self) -> Vec<ScmpArch>;
 
-    /// Get the associated `ScmpSyscall` - used to build the `HashMap`
-    fn get_syscall(&self) -> anyhow::Result<ScmpSyscall>;
+    /// Get the name of the syscall as used by seccomp profiles
+    fn get_name(&self) -> String;
+
+    /// Whether the handler emulates permission checks, which is only enabled by `Config::permission_checks`
+    fn is_permission_check(&self) -> bool;
 }
 
-/// Implements the `Syscall` Trait to ease the implementation for a new Syscall
-/// Transforms all Arguments to the target type. This helps to avoid TOCTOU style attacks by forcing the Implementation to read all Arguments first.
-#[macro_export]
-macro_rules! syscall {
-    ($name:ident {
-    $($arg:ident: $arg_type:ty),*
-    }, $self:ident $body:block) => {
-        pub struct $name;
-
-        struct SyscallData {
-            #[allow(unused)]
-            req: libseccomp::ScmpNotifReq,
-            #[allow(unused)]
-            fd: libseccomp::ScmpFd,
-            $(
-            $arg: $arg_type,
-            )*
-        }
+/// All registered `Syscall` implementations that are enabled in `config`.
+/// Permission checks are included without `Config::permission_checks` as well, so the profile does not depend on it,
+/// see `Notification::permission_checks`.
+pub fn handlers(config: &Config) -> impl Iterator<Item = &'static dyn Syscall> + '_ {
+    inventory::iter::<&dyn Syscall>
+        .into_iter()
+        .copied()
+        .filter(|syscall| config.is_enabled(&syscall.get_name()))
+}
 
-        impl SyscallData {
-            fn new(req: libseccomp::ScmpNotifReq, fd: libseccomp::ScmpFd, $($arg: $arg_type),*) -> Result<Self, $crate::Error> {
-                Ok(Self {
-                    req,
-                    fd,
-                    $($arg),*
-                })
+/// Builds the `HashMap` used to look up the `Syscall` of a `ScmpNotifReq` by its `arch` and `syscall`
+/// Syscalls that do not exist on an architecture are skipped
+pub fn registry(
+    config: &Config,
+) -> anyhow::Result<HashMap<(ScmpArch, ScmpSyscall), &'static dyn Syscall>> {
+    let supported = supported_archs();
+    let mut syscalls = HashMap::new();
+    for syscall in handlers(config) {
+        for arch in syscall.get_archs() {
+            if !supported.contains(&arch) {
+                continue;
+            }
+            let number = syscall.get_syscall(arch)?;
+            // libseccomp returns negative pseudo syscall numbers for syscalls missing on `arch`
+            if i32::from(number) >= 0_i32 {
+                syscalls.insert((arch, number), syscall);
             }
-            fn execute_internal($self: Self) -> Result<i64, $crate::Error> $body
         }
-        #[allow(clippy::semicolon_outside_block)]
-        impl $crate::syscall::Syscall for $name {
-            fn execute(&self, req: libseccomp::ScmpNotifReq, fd: libseccomp::ScmpFd) -> Result<i64, $crate::Error> {
-                $crate::arg!(0_usize, req, fd, $($arg: $arg_type),*);
-                SyscallData::new(req, fd, $($arg),*)?.execute_internal()
+    }
+    Ok(syscalls)
+}
+
+/// How a notified syscall is answered
+#[allow(clippy::exhaustive_enums)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The syscall returns the value without being executed
+    Return(i64),
+    /// The kernel executes the syscall itself with `SECCOMP_USER_NOTIF_FLAG_CONTINUE`.
+    /// The arguments might have changed since the handler read them, so only use it where the kernel's result is the emulated one.
+    Continue,
+    /// The handler already answered the notification, e.g. with `Notification::send_fd`
+    Responded,
+}
+
+impl From<i64> for Outcome {
+    fn from(val: i64) -> Self {
+        Self::Return(val)
+    }
+}
+
+/// Largest errno the kernel accepts in a seccomp response
+const MAX_ERRNO: i32 = 4095;
+
+/// The notification a handler implemented with `#[subuidless::syscall]` runs for
+#[allow(clippy::exhaustive_structs)]
+#[derive(Debug, Clone)]
+pub struct Notification {
+    /// The request as received from seccomp
+    pub req: ScmpNotifReq,
+    /// The notify fd the request was received on
+    pub fd: ScmpFd,
+    /// Whether `Config::permission_checks` is enabled, so handlers apply the privilege rules of the kernel.
+    /// Otherwise the notifications of permission checks are passed on to the kernel.
+    pub permission_checks: bool,
+}
+
+impl Notification {
+    /// The syscall argument at `index`, one of the `args: [u64;6]` array
+    pub fn arg(&self, index: usize) -> Result<MaybeRemote, crate::Error> {
+        let pid = Pid::from_raw(i32::try_from(self.req.pid).map_err(attach(Errno::EINVAL))?);
+        let arg = self
+            .req
+            .data
+            .args
+            .get(index)
+            .copied()
+            .ok_or(Errno::EINVAL)?;
+        Ok(MaybeRemote::new(
+            pid,
+            self.req.data.arch,
+            arg,
+            self.fd,
+            self.req.id,
+        ))
+    }
+
+    /// Whether the caller uses a native ABI rather than a 32-bit compat one
+    #[must_use]
+    pub fn is_native(&self) -> bool {
+        native_archs().contains(&self.req.data.arch)
+    }
+
+    /// Duplicates the file descriptor `fd` of the caller with `pidfd_getfd(2)`,
+    /// which unlike opening `/proc/<pid>/fd/<fd>` works for sockets as well
+    pub fn get_fd(&self, fd: RawFd) -> Result<OwnedFd, crate::Error> {
+        let pid = i32::try_from(self.req.pid).map_err(attach(Errno::EINVAL))?;
+        let pidfd = pidfd_open(
+            RawPid::from_raw(pid).ok_or(Errno::ESRCH)?,
+            PidfdFlags::empty(),
+        )?;
+        let duplicate = pidfd_getfd(&pidfd, fd, PidfdGetfdFlags::empty())?;
+        // The pid might belong to another process if the caller exited in the meantime
+        notify_id_valid(self.fd, self.req.id).map_err(attach(Errno::EPERM))?;
+        Ok(duplicate)
+    }
+
+    /// Answers the notification with the `result` of its handler, unless the handler already responded
+    pub fn respond(&self, result: Result<Outcome, crate::Error>) {
+        let id = self.req.id;
+        let response = match result {
+            Ok(Outcome::Return(val)) => {
+                ScmpNotifResp::new_val(id, val, ScmpNotifRespFlags::empty())
             }
-            fn get_syscall(&self) -> anyhow::Result<libseccomp::ScmpSyscall> {
-                Ok(libseccomp::ScmpSyscall::from_name(&stringify!($name).to_lowercase())?)
+            Ok(Outcome::Continue) => ScmpNotifResp::new_val(id, 0, ScmpNotifRespFlags::CONTINUE),
+            Ok(Outcome::Responded) => return,
+            Err(err) => {
+                debug!("Syscall {id} failed: {err:#}");
+                let errno = match i32::from(err) {
+                    errno @ 1_i32..=MAX_ERRNO => errno,
+                    errno => {
+                        warn!("Handler returned the invalid errno {errno}");
+                        EIO
+                    }
+                };
+                ScmpNotifResp::new_error(id, errno.neg(), ScmpNotifRespFlags::empty())
             }
-        }
+        };
+        respond(self.fd, response);
+    }
 
-        inventory::submit! {
-            &$name as &dyn $crate::syscall::Syscall
-        }
-    };
+    /// Installs `fd` in the caller and returns its number there, without answering the notification
+    pub fn add_fd<Fd: AsFd>(&self, fd: Fd, flags: OFlag) -> Result<RawFd, crate::Error> {
+        add_fd(self.fd, self.req.id, fd, flags)
+    }
+
+    /// Installs `fd` in the caller and answers the notification with its number, like `openat` would return it.
+    /// `flags` may only contain `O_CLOEXEC`.
+    pub fn send_fd<Fd: AsFd>(&self, fd: Fd, flags: OFlag) -> Result<Outcome, crate::Error> {
+        Ok(match send_fd(self.fd, self.req.id, fd, flags)? {
+            None => Outcome::Responded,
+            Some(newfd) => Outcome::Return(newfd.into()),
+        })
+    }
 }
 
-/// Constructs a `MaybeRemote` and tries to Transform it into the target type
-/// Uses recursive Macro calls to access attributes in the `args: [u64;6]` array
-#[macro_export]
-macro_rules! arg {
-    ($idx:expr, $req:ident, $fd:ident, $var:ident: $var_type:ty) => {
-        let $var: $var_type = $crate::mem::MaybeRemote::new(
-            nix::unistd::Pid::from_raw(i32::try_from($req.pid).map_err($crate::error::attach(nix::errno::Errno::EINVAL))?),
-            $req.data.args[$idx],
-            $fd,
-            $req.id
-        ).try_into()?;
-    };
-
-    ( $idx:expr, $req:ident, $fd:ident, $var:ident: $var_type:ty, $($t_var:ident: $t_var_type:ty),*) => {
-        $crate::arg!($idx, $req, $fd, $var: $var_type);
-        $crate::arg!($idx + 1_usize,  $req, $fd, $($t_var: $t_var_type),*);
-    };
+/// Sends `response` on the notify fd `fd`, a notification that is no longer valid is not an error
+pub fn respond(fd: ScmpFd, response: ScmpNotifResp) {
+    match response.respond(fd) {
+        Ok(()) => {}
+        Err(err) if is_stale(&err) => debug!("Notification {} is no longer valid", response.id),
+        Err(err) => warn!("Could not respond to notification {}: {err}", response.id),
+    }
 }
+
+/// Whether `err` was caused by a notification that is no longer valid,
+/// because the syscall was interrupted by a signal or the process was killed
+#[must_use]
+pub fn is_stale(err: &SeccompError) -> bool {
+    err.errno() == Some(SeccompErrno::ENOENT)
+        || err
+            .sysrawrc()
+            .is_some_and(|rc| Errno::from_raw(rc.saturating_abs()) == Errno::ENOENT)
+}
+
 inventory::collect!(&'static dyn Syscall);
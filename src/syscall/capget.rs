@@ -0,0 +1,131 @@
+use nix::errno::Errno;
+
+use super::{Notification, Outcome};
+use crate::abi::{CapUserData, CapUserHeader};
+use crate::identity::{Capabilities, Identity};
+use crate::mem::{RemoteSlice, RemoteStruct};
+use crate::pidns::status;
+use crate::Error;
+
+/// `_LINUX_CAPABILITY_VERSION_1`, with 32 capabilities
+const VERSION_1: u32 = 0x1998_0330;
+/// `_LINUX_CAPABILITY_VERSION_2`, deprecated in favor of version 3
+const VERSION_2: u32 = 0x2007_1026;
+/// `_LINUX_CAPABILITY_VERSION_3`, with 64 capabilities
+const VERSION_3: u32 = 0x2008_0522;
+
+/// Reports the emulated capabilities of the caller, the kernel answers for other processes
+#[subuidless::syscall]
+fn capget(
+    notification: &Notification,
+    hdrp: RemoteStruct<CapUserHeader>,
+    datap: RemoteSlice<CapUserData>,
+) -> Result<Outcome, Error> {
+    let header = hdrp.read()?;
+    let len = validate_version(header, hdrp)?;
+    // A null pointer only probes the version, an unknown one was already replaced
+    if usize::try_from(notification.arg(1)?)? == 0 {
+        return Ok(Outcome::Return(0));
+    }
+    let len = len.ok_or(Errno::EINVAL)?;
+    if header.pid < 0_i32 {
+        return Err(Errno::EINVAL.into());
+    }
+    if !is_caller(notification, header.pid)? {
+        return Ok(Outcome::Continue);
+    }
+    let Some(identity) = Identity::tracked(notification.req.pid)? else {
+        return Ok(Outcome::Continue);
+    };
+
+    let caps = identity.caps;
+    let (effective, permitted, inheritable) = (
+        split(caps.effective),
+        split(caps.permitted),
+        split(caps.inheritable),
+    );
+    let data = [
+        CapUserData {
+            effective: effective.0,
+            permitted: permitted.0,
+            inheritable: inheritable.0,
+        },
+        CapUserData {
+            effective: effective.1,
+            permitted: permitted.1,
+            inheritable: inheritable.1,
+        },
+    ];
+    datap
+        .with_len(len)
+        .write(data.get(..len).unwrap_or_default())?;
+    Ok(Outcome::Return(0))
+}
+
+/// Changes the emulated capabilities of the caller, which may not change the ones of other processes
+#[subuidless::syscall]
+fn capset(
+    notification: &Notification,
+    hdrp: RemoteStruct<CapUserHeader>,
+    datap: RemoteSlice<CapUserData>,
+) -> Result<i64, Error> {
+    let header = hdrp.read()?;
+    let len = validate_version(header, hdrp)?.ok_or(Errno::EINVAL)?;
+    if header.pid != 0_i32 && !is_caller(notification, header.pid)? {
+        return Err(Errno::EPERM.into());
+    }
+
+    let data = datap.with_len(len).read()?;
+    // Version 1 only passes the lower 32 capabilities, the upper ones are dropped
+    let low = data.first().copied().unwrap_or_default();
+    let high = data.get(1).copied().unwrap_or_default();
+    let caps = Capabilities {
+        effective: join(low.effective, high.effective),
+        permitted: join(low.permitted, high.permitted),
+        inheritable: join(low.inheritable, high.inheritable),
+    };
+
+    Identity::update(notification.req.pid, |identity| Ok(identity.capset(caps)?))?;
+    Ok(0)
+}
+
+/// The number of `CapUserData` of the version in `header`, `None` for an unknown version.
+/// An unknown version is replaced with the preferred one, as the kernel tells the caller which one to use.
+fn validate_version(
+    header: CapUserHeader,
+    hdrp: RemoteStruct<CapUserHeader>,
+) -> Result<Option<usize>, Error> {
+    match header.version {
+        VERSION_1 => Ok(Some(1)),
+        VERSION_2 | VERSION_3 => Ok(Some(2)),
+        _ => {
+            hdrp.write(CapUserHeader {
+                version: VERSION_3,
+                ..header
+            })?;
+            Ok(None)
+        }
+    }
+}
+
+/// Whether `pid` of the PID namespace of the caller is the caller itself
+fn is_caller(notification: &Notification, pid: i32) -> Result<bool, Error> {
+    if pid == 0_i32 {
+        return Ok(true);
+    }
+    let status = status(notification.req.pid)?;
+    // The last entry of `NSpid` is the pid in the namespace of the caller
+    Ok(status.nspid.and_then(|nspid| nspid.last().copied()) == Some(pid))
+}
+
+/// The lower and upper 32 capabilities of `caps`
+fn split(caps: u64) -> (u32, u32) {
+    let low = u32::try_from(caps & 0xffff_ffff).unwrap_or_default();
+    let high = u32::try_from(caps.checked_shr(32).unwrap_or_default()).unwrap_or_default();
+    (low, high)
+}
+
+/// The capabilities of the lower and upper 32 bits
+fn join(low: u32, high: u32) -> u64 {
+    u64::from(low) | u64::from(high).checked_shl(32).unwrap_or_default()
+}
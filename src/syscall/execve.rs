@@ -0,0 +1,93 @@
+use std::fs::File;
+use std::io::Read;
+use std::os::fd::{AsRawFd, OwnedFd, RawFd};
+use std::path::PathBuf;
+
+use nix::fcntl::AtFlags;
+use nix::libc::{S_IFMT, S_IFREG, S_ISGID, S_ISUID, S_IXGRP};
+use nix::sys::stat::fstat;
+use rustix::fs::{fstatvfs, StatVfsMountFlags};
+
+use super::fstatat::{emulated_ids, stored_ids};
+use super::setxattr::file_capabilities;
+use super::{Notification, Outcome};
+use crate::identity::{Executable, Identity};
+use crate::pidns::status;
+use crate::resolve::{fd_path, resolve};
+use crate::xattr::{find_xa_value_fd, Emulated};
+use crate::Error;
+
+/// Changes the emulated credentials like a set-user-ID or set-group-ID executable with an emulated owner would,
+/// once the kernel ran it. The real credentials stay the same.
+#[subuidless::syscall]
+fn execve(notification: &Notification, pathname: PathBuf) -> Result<Outcome, Error> {
+    // The kernel reports why the executable can not be run
+    if let Ok(file) = resolve(notification, None, &pathname, AtFlags::empty()) {
+        prepare(notification.req.pid, &file)?;
+    }
+    Ok(Outcome::Continue)
+}
+
+/// Changes the emulated credentials like a set-user-ID or set-group-ID executable with an emulated owner would,
+/// once the kernel ran it. The real credentials stay the same.
+#[subuidless::syscall]
+fn execveat(
+    notification: &Notification,
+    #[fd] dirfd: Option<RawFd>,
+    pathname: PathBuf,
+    _argv: usize,
+    _envp: usize,
+    flags: AtFlags,
+) -> Result<Outcome, Error> {
+    if let Ok(file) = resolve(notification, dirfd, &pathname, flags) {
+        prepare(notification.req.pid, &file)?;
+    }
+    Ok(Outcome::Continue)
+}
+
+/// Computes the credentials process `pid` has after it ran `file`,
+/// they are tracked once its `execve(2)` succeeded
+fn prepare(pid: u32, file: &OwnedFd) -> Result<(), Error> {
+    let stat = fstat(file.as_raw_fd())?;
+    if stat.st_mode & S_IFMT != S_IFREG {
+        return Ok(());
+    }
+    let tracked = Identity::tracked(pid)?;
+    let owned = stored_ids(file)?.is_some();
+    let caps = find_xa_value_fd(file, Emulated::Capability)
+        .ok()
+        .flatten()
+        .and_then(|value| file_capabilities(&value));
+    // Without emulated credentials, owner or file capabilities the kernel computes the same credentials
+    if tracked.is_none() && !(owned && stat.st_mode & (S_ISUID | S_ISGID) != 0) && caps.is_none() {
+        return Ok(());
+    }
+
+    let status = status(pid)?;
+    let nosuid = fstatvfs(file)?.f_flag.contains(StatVfsMountFlags::NOSUID);
+    // Like the kernel, the interpreter of a script decides about the credentials
+    let privileged = !nosuid && status.nonewprivs != Some(1) && !is_script(file);
+    let (uid, gid) = emulated_ids(pid, file, &stat)?;
+    let executable = Executable {
+        setuid: (privileged && stat.st_mode & S_ISUID != 0).then_some(uid),
+        setgid: (privileged && stat.st_mode & (S_ISGID | S_IXGRP) == S_ISGID | S_IXGRP)
+            .then_some(gid),
+        caps: caps.filter(|_| privileged),
+    };
+
+    let mut identity = match tracked {
+        Some(identity) => identity,
+        None => Identity::read(pid)?,
+    };
+    identity.execve(&executable, status.capbnd.unwrap_or(u64::MAX));
+    identity.save_on_exec(pid, (stat.st_dev, stat.st_ino))
+}
+
+/// Whether `file` starts with `#!`
+fn is_script(file: &OwnedFd) -> bool {
+    let mut magic = [0_u8; 2];
+    File::open(fd_path(file))
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok()
+        && &magic == b"#!"
+}
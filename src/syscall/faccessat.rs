@@ -0,0 +1,92 @@
+use std::os::fd::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+
+use nix::errno::Errno;
+use nix::fcntl::AtFlags;
+use nix::libc::{S_IFBLK, S_IFCHR, S_IFIFO, S_IFMT, S_IFSOCK};
+use nix::sys::stat::fstat;
+use nix::unistd::AccessFlags;
+use rustix::fs::{fstatvfs, StatVfsMountFlags};
+
+use super::fstatat::stored_ids;
+use super::{Notification, Outcome};
+use crate::creds::Credentials;
+use crate::resolve::resolve;
+use crate::Error;
+
+/// Checks the permissions of the real ids against the emulated ownership
+#[subuidless::syscall(permission_check)]
+fn access(
+    notification: &Notification,
+    pathname: PathBuf,
+    mode: AccessFlags,
+) -> Result<Outcome, Error> {
+    check_access(notification, None, &pathname, mode, AtFlags::empty())
+}
+
+/// Checks the permissions of the real ids against the emulated ownership
+#[subuidless::syscall(permission_check)]
+fn faccessat(
+    notification: &Notification,
+    #[fd] dirfd: Option<RawFd>,
+    pathname: PathBuf,
+    mode: AccessFlags,
+) -> Result<Outcome, Error> {
+    check_access(notification, dirfd, &pathname, mode, AtFlags::empty())
+}
+
+/// Checks the permissions of the real or, with `AT_EACCESS`, the effective ids against the emulated ownership
+#[subuidless::syscall(permission_check)]
+fn faccessat2(
+    notification: &Notification,
+    #[fd] dirfd: Option<RawFd>,
+    pathname: PathBuf,
+    mode: AccessFlags,
+    flags: AtFlags,
+) -> Result<Outcome, Error> {
+    if !(AtFlags::AT_EACCESS | AtFlags::AT_SYMLINK_NOFOLLOW | AtFlags::AT_EMPTY_PATH)
+        .contains(flags)
+    {
+        return Err(Errno::EINVAL.into());
+    }
+    check_access(notification, dirfd, &pathname, mode, flags)
+}
+
+/// Answers an `access(2)` style check of `mode` on `path`.
+/// Files without emulated ownership are checked by the kernel.
+fn check_access(
+    notification: &Notification,
+    dirfd: Option<RawFd>,
+    path: &Path,
+    mode: AccessFlags,
+    flags: AtFlags,
+) -> Result<Outcome, Error> {
+    let pid = notification.req.pid;
+    let file = resolve(notification, dirfd, path, flags)?;
+    let Some((uid, gid)) = stored_ids(&file)? else {
+        return Ok(Outcome::Continue);
+    };
+    if mode == AccessFlags::F_OK {
+        return Ok(Outcome::Return(0));
+    }
+
+    let stat = fstat(file.as_raw_fd())?;
+    let special = matches!(
+        stat.st_mode & S_IFMT,
+        S_IFCHR | S_IFBLK | S_IFIFO | S_IFSOCK
+    );
+    // Writing to devices, pipes and sockets does not change the read-only filesystem they are on
+    if mode.contains(AccessFlags::W_OK)
+        && !special
+        && fstatvfs(&file)?.f_flag.contains(StatVfsMountFlags::RDONLY)
+    {
+        return Err(Errno::EROFS.into());
+    }
+
+    let creds = Credentials::read(pid, flags.contains(AtFlags::AT_EACCESS))?;
+    if creds.may_access(uid, gid, stat.st_mode, mode) {
+        Ok(Outcome::Return(0))
+    } else {
+        Err(Errno::EACCES.into())
+    }
+}
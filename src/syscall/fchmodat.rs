@@ -0,0 +1,78 @@
+use std::os::fd::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+
+use nix::errno::Errno;
+use nix::fcntl::AtFlags;
+use nix::libc::{mode_t, S_ISGID};
+use nix::sys::stat::{self, fstat, FchmodatFlags, Mode};
+
+use super::fstatat::emulated_ids;
+use super::{Notification, Outcome};
+use crate::creds::{Credentials, CAP_FSETID};
+use crate::resolve::{fd_path, resolve};
+use crate::Error;
+
+/// Changes the mode if the caller owns the file by its emulated ownership
+#[subuidless::syscall(permission_check)]
+fn chmod(notification: &Notification, pathname: PathBuf, mode: mode_t) -> Result<Outcome, Error> {
+    change_mode(notification, None, &pathname, mode, AtFlags::empty())
+}
+
+/// Changes the mode if the caller owns the file by its emulated ownership
+#[subuidless::syscall(permission_check)]
+fn fchmod(notification: &Notification, #[fd] fd: RawFd, mode: mode_t) -> Result<Outcome, Error> {
+    change_mode(
+        notification,
+        Some(fd),
+        Path::new(""),
+        mode,
+        AtFlags::AT_EMPTY_PATH,
+    )
+}
+
+/// Changes the mode if the caller owns the file by its emulated ownership
+#[subuidless::syscall(permission_check)]
+fn fchmodat(
+    notification: &Notification,
+    #[fd] dirfd: Option<RawFd>,
+    pathname: PathBuf,
+    mode: mode_t,
+) -> Result<Outcome, Error> {
+    change_mode(notification, dirfd, &pathname, mode, AtFlags::empty())
+}
+
+/// Applies the privilege rules of `chmod(2)` with the emulated ownership:
+/// only the owner or a caller with `CAP_FOWNER` may change the mode,
+/// and the set-group-ID bit is dropped unless the caller is in the group of the file or has `CAP_FSETID`.
+fn change_mode(
+    notification: &Notification,
+    dirfd: Option<RawFd>,
+    path: &Path,
+    mode: mode_t,
+    flags: AtFlags,
+) -> Result<Outcome, Error> {
+    let pid = notification.req.pid;
+    let file = resolve(notification, dirfd, path, flags)?;
+    let (uid, gid) = emulated_ids(pid, &file, &fstat(file.as_raw_fd())?)?;
+    let creds = Credentials::read(pid, true)?;
+    if !creds.is_owner(uid) {
+        return Err(Errno::EPERM.into());
+    }
+
+    let requested = mode & 0o7777;
+    let mut allowed = requested;
+    if !creds.in_group(gid) && !creds.has_capability(CAP_FSETID) {
+        allowed &= !S_ISGID;
+    }
+    // The kernel applies an unchanged mode with the privileges the caller really has
+    if allowed == requested {
+        return Ok(Outcome::Continue);
+    }
+    stat::fchmodat(
+        None,
+        &fd_path(&file),
+        Mode::from_bits_truncate(allowed),
+        FchmodatFlags::FollowSymlink,
+    )?;
+    Ok(Outcome::Return(0))
+}
@@ -1,27 +1,70 @@
-use std::fs::File;
-use std::path::{Path, PathBuf};
+use std::os::fd::{AsRawFd, RawFd};
+use std::path::PathBuf;
 
+use nix::errno::Errno;
 use nix::fcntl::AtFlags;
 use nix::libc::{gid_t, uid_t};
-use nix::unistd::chdir;
+use nix::sys::stat::fstat;
 
-use crate::syscall;
-use crate::xattr::set_xa_user;
+use super::fstatat::emulated_ids;
+use super::Notification;
+use crate::creds::{Credentials, CAP_CHOWN};
+use crate::resolve::resolve;
+use crate::xattr::set_xa_user_fd;
+use crate::Error;
 
-syscall!(Fchownat {
-    _dirfd: Option<File>,
+/// Stores the new owner and group in the xAttribute instead of changing them
+#[subuidless::syscall]
+fn fchownat(
+    notification: &Notification,
+    #[fd] dirfd: Option<RawFd>,
     pathname: PathBuf,
-    owner: uid_t,
-    group: gid_t,
-    flags: AtFlags
-},
-    self {
-        if !self.pathname.is_absolute() {
-            chdir(Path::new(&format!("/proc/{}/cwd", self.req.pid)))?;
+    owner: Option<uid_t>,
+    group: Option<gid_t>,
+    flags: AtFlags,
+) -> Result<i64, Error> {
+    let pid = notification.req.pid;
+    let file = resolve(notification, dirfd, &pathname, flags)?;
+    let current = || emulated_ids(pid, &file, &fstat(file.as_raw_fd())?);
+
+    if notification.permission_checks {
+        let creds = Credentials::read(pid, true)?;
+        if !may_chown(&creds, current()?, owner, group) {
+            return Err(Errno::EPERM.into());
+        }
+    }
+
+    // An id of -1 keeps the current one
+    let (owner, group) = match (owner, group) {
+        (None, None) => return Ok(0),
+        (Some(owner), Some(group)) => (owner, group),
+        (owner, group) => {
+            let (uid, gid) = current()?;
+            (owner.unwrap_or(uid), group.unwrap_or(gid))
         }
+    };
 
-        let follow = !AtFlags::contains(&self.flags, AtFlags::AT_SYMLINK_NOFOLLOW);
+    match set_xa_user_fd(&file, owner, group) {
+        // The file was swapped while the xAttribute was written
+        Err(err @ Error::Anyhow(Errno::ESTALE, _)) => Err(err),
+        _ => Ok(0),
+    }
+}
 
-        let _err = set_xa_user(&self.pathname, follow, self.owner, self.group);
-        Ok(0)
-});
+/// The privilege rules of `chown(2)`: without `CAP_CHOWN` only the owner may change the group,
+/// and only to one of its groups, while the owner may not be changed at all
+fn may_chown(
+    creds: &Credentials,
+    (uid, gid): (uid_t, gid_t),
+    owner: Option<uid_t>,
+    group: Option<gid_t>,
+) -> bool {
+    if creds.has_capability(CAP_CHOWN) {
+        return true;
+    }
+    let owner_ok = owner.map_or(true, |owner| creds.uid == uid && owner == uid);
+    let group_ok = group.map_or(true, |group| {
+        creds.uid == uid && (group == gid || creds.in_group(group))
+    });
+    owner_ok && group_ok
+}
@@ -1,34 +1,78 @@
-use std::fs::File;
-use std::os::fd::AsRawFd;
+use std::os::fd::{AsRawFd, OwnedFd, RawFd};
 use std::path::{Path, PathBuf};
 
+use nix::errno::Errno;
 use nix::fcntl::AtFlags;
-use nix::sys::stat::{fstatat, FileStat};
-use nix::unistd::chdir;
+use nix::libc::{gid_t, uid_t};
+use nix::sys::stat::{fstat, FileStat};
 
-use crate::mem::RemoteStruct;
-use crate::syscall;
-use crate::xattr::get_xa_user;
+use super::{Notification, Outcome};
+use crate::abi::native_archs;
+use crate::error::attach;
+use crate::idmap::{IdMap, OVERFLOW_ID};
+use crate::resolve::resolve;
+use crate::xattr::find_xa_user_fd;
+use crate::Error;
 
-syscall!(Newfstatat {
-    dirfd: Option<File>,
+/// Stats a file with the ids the caller is supposed to see,
+/// files without emulated ownership are left to the kernel
+#[subuidless::syscall(archs = native_archs())]
+fn newfstatat(
+    notification: &Notification,
+    #[fd] dirfd: Option<RawFd>,
     pathname: PathBuf,
-    remote_stat: RemoteStruct<FileStat>,
-    flags: AtFlags
-},
-self {
-    if !self.pathname.is_absolute() {
-        chdir(Path::new(&format!("/proc/{}/cwd", self.req.pid)))?;
-    }
-    let follow = !AtFlags::contains(&self.flags, AtFlags::AT_SYMLINK_NOFOLLOW);
+    #[out] statbuf: &mut FileStat,
+    flags: AtFlags,
+) -> Result<Outcome, Error> {
+    let Some(stat) = stat_at(notification, dirfd, &pathname, flags)? else {
+        return Ok(Outcome::Continue);
+    };
+    *statbuf = stat;
+    Ok(Outcome::Return(0))
+}
+
+/// Stats `path` as the caller of `notification` would see it with the ids stored in the xAttribute.
+/// Returns `None` if the ownership of the file is not emulated, so the kernel reports it as well.
+pub(super) fn stat_at(
+    notification: &Notification,
+    dirfd: Option<RawFd>,
+    path: &Path,
+    flags: AtFlags,
+) -> Result<Option<FileStat>, Error> {
+    let file = resolve(notification, dirfd, path, flags)?;
 
-    let mut stat = fstatat(self.dirfd.map(|file|file.as_raw_fd()), &self.pathname, self.flags)?;
+    let Some((uid, gid)) = stored_ids(&file)? else {
+        return Ok(None);
+    };
+    let mut stat = fstat(file.as_raw_fd())?;
+    (stat.st_uid, stat.st_gid) = (uid, gid);
+    Ok(Some(stat))
+}
 
-    if let Ok((uid,gid)) = get_xa_user(&self.pathname, follow) {
-        stat.st_uid = uid;
-        stat.st_gid = gid;
+/// The ids stored in the xAttribute of `file`, `None` if there are none or they can not be read,
+/// unless the file was swapped while the xAttribute was read
+pub(super) fn stored_ids(file: &OwnedFd) -> Result<Option<(uid_t, gid_t)>, Error> {
+    match find_xa_user_fd(file) {
+        Err(err @ Error::Anyhow(Errno::ESTALE, _)) => Err(err),
+        Ok(ids) => Ok(ids),
+        Err(_) => Ok(None),
     }
+}
 
-    self.remote_stat.write(stat)?;
-    Ok(0)
-});
+/// The ids of `file` as seen by process `pid`: the ones stored in the xAttribute or else `stat` mapped into its user namespace
+pub(super) fn emulated_ids(
+    pid: u32,
+    file: &OwnedFd,
+    stat: &FileStat,
+) -> Result<(uid_t, gid_t), Error> {
+    if let Some(ids) = stored_ids(file)? {
+        return Ok(ids);
+    }
+    // The caller sees the ids through the mapping of its user namespace
+    let uid_map = IdMap::read(format!("/proc/{pid}/uid_map")).map_err(attach(Errno::ESRCH))?;
+    let gid_map = IdMap::read(format!("/proc/{pid}/gid_map")).map_err(attach(Errno::ESRCH))?;
+    Ok((
+        uid_map.to_inside(stat.st_uid).unwrap_or(OVERFLOW_ID),
+        gid_map.to_inside(stat.st_gid).unwrap_or(OVERFLOW_ID),
+    ))
+}
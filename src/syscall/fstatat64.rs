@@ -0,0 +1,39 @@
+use std::os::fd::RawFd;
+use std::path::PathBuf;
+
+use libseccomp::ScmpArch;
+use nix::errno::Errno;
+use nix::fcntl::AtFlags;
+use nix::sys::stat::FileStat;
+
+use super::fstatat::stat_at;
+use super::{Notification, Outcome};
+use crate::abi::{Stat64Arm, Stat64I386};
+use crate::mem::RemoteStruct;
+use crate::Error;
+
+/// Stats a file for the 32-bit compat ABIs, whose `struct stat64` differs on every architecture.
+/// Files without emulated ownership are left to the kernel.
+#[subuidless::syscall(archs = [ScmpArch::X86, ScmpArch::Arm])]
+fn fstatat64(
+    notification: &Notification,
+    #[fd] dirfd: Option<RawFd>,
+    pathname: PathBuf,
+    statbuf: RemoteStruct<FileStat>,
+    flags: AtFlags,
+) -> Result<Outcome, Error> {
+    let Some(stat) = stat_at(notification, dirfd, &pathname, flags)? else {
+        return Ok(Outcome::Continue);
+    };
+    let arch = notification.req.data.arch;
+    if arch == ScmpArch::X86 {
+        statbuf
+            .cast::<Stat64I386>()
+            .write(Stat64I386::from(&stat))?;
+    } else if arch == ScmpArch::Arm {
+        statbuf.cast::<Stat64Arm>().write(Stat64Arm::from(&stat))?;
+    } else {
+        return Err(Errno::ENOSYS.into());
+    }
+    Ok(Outcome::Return(0))
+}
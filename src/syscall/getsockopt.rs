@@ -0,0 +1,137 @@
+use std::fs::metadata;
+use std::mem::size_of;
+use std::os::fd::{AsRawFd, OwnedFd, RawFd};
+use std::os::unix::fs::MetadataExt;
+use std::ptr;
+
+use anyhow::Context;
+use nix::errno::Errno;
+use nix::libc::{self, socklen_t, ucred, SOL_SOCKET, SO_PEERCRED};
+
+use super::{Notification, Outcome};
+use crate::error::attach;
+use crate::identity::Identity;
+use crate::idmap::{IdMap, OVERFLOW_ID};
+use crate::mem::{zeroed, RemoteBuf, RemoteStruct};
+use crate::pidns::{namespace, status};
+use crate::Error;
+
+/// Reports the emulated effective ids of the peer for `SO_PEERCRED`, other options are left to the kernel.
+/// The ids are the current ones of the peer, not the ones it had when the connection was established.
+#[subuidless::syscall]
+fn getsockopt(
+    notification: &Notification,
+    #[fd] sockfd: RawFd,
+    level: i32,
+    optname: i32,
+    optval: RemoteBuf,
+    optlen: RemoteStruct<socklen_t>,
+) -> Result<Outcome, Error> {
+    if level != SOL_SOCKET || optname != SO_PEERCRED {
+        return Ok(Outcome::Continue);
+    }
+    let socket = notification.get_fd(sockfd)?;
+    let peer = peer_credentials(&socket)?;
+    let len = i32::try_from(optlen.read()?).map_err(attach(Errno::EINVAL))?;
+
+    let cred = translate_ucred(notification.req.pid, peer, PeerIds::Effective)?;
+    let written = optval
+        .with_len(usize::try_from(len).map_err(attach(Errno::EINVAL))?)
+        .write_truncated(&cred)?;
+    optlen.write(socklen_t::try_from(written).map_err(attach(Errno::EINVAL))?)?;
+    Ok(Outcome::Return(0))
+}
+
+/// The ids `translate_ucred` reports for a peer in the same user namespace
+#[derive(Debug, Clone, Copy)]
+pub(super) enum PeerIds {
+    /// The emulated effective ids
+    Effective,
+    /// The emulated real ids
+    Real,
+    /// The ids the kernel reported
+    Kernel,
+}
+
+/// Translates `cred`, as the kernel reports it to the daemon, for process `pid`:
+/// the pid into its PID namespace, 0 if the peer is not visible there, and the ids into its user namespace.
+/// A peer in the same user namespace reports the emulated ids selected by `ids`.
+pub(super) fn translate_ucred(pid: u32, cred: ucred, ids: PeerIds) -> Result<ucred, Error> {
+    let uid_map = IdMap::read(format!("/proc/{pid}/uid_map")).map_err(attach(Errno::ESRCH))?;
+    let gid_map = IdMap::read(format!("/proc/{pid}/gid_map")).map_err(attach(Errno::ESRCH))?;
+    let mut translated = ucred {
+        pid: 0,
+        uid: uid_map.to_inside(cred.uid).unwrap_or(OVERFLOW_ID),
+        gid: gid_map.to_inside(cred.gid).unwrap_or(OVERFLOW_ID),
+    };
+    let Some(peer) = u32::try_from(cred.pid).ok().filter(|&peer| peer != 0) else {
+        return Ok(translated);
+    };
+    // Only the ids reported by the kernel are known for a peer that exited
+    let Ok(peer_nspid) = nspid(peer) else {
+        return Ok(translated);
+    };
+
+    // `NSpid` starts with the pid in the namespace of the daemon, the entry at the depth of the caller is the pid there
+    // if the peer is part of the namespace of the caller, and not of a sibling at the same depth
+    let depth = nspid(pid)?.len();
+    let visible = peer_nspid
+        .len()
+        .checked_sub(depth)
+        .and_then(|up| namespace(peer, up).ok())
+        == Some(namespace(pid, 0)?);
+    if visible {
+        translated.pid = depth
+            .checked_sub(1)
+            .and_then(|index| peer_nspid.get(index))
+            .copied()
+            .unwrap_or_default();
+    }
+    if user_namespace(peer).ok() == Some(user_namespace(pid)?) {
+        if let Ok(identity) = Identity::get(peer) {
+            let emulated = match ids {
+                PeerIds::Effective => Some((identity.uid.effective, identity.gid.effective)),
+                PeerIds::Real => Some((identity.uid.real, identity.gid.real)),
+                PeerIds::Kernel => None,
+            };
+            if let Some((uid, gid)) = emulated {
+                translated.uid = uid;
+                translated.gid = gid;
+            }
+        }
+    }
+    Ok(translated)
+}
+
+/// The credentials of the peer of `socket` as the kernel reports them to the daemon
+fn peer_credentials(socket: &OwnedFd) -> Result<ucred, Error> {
+    let mut cred = zeroed::<ucred>();
+    let mut len = socklen_t::try_from(size_of::<ucred>()).map_err(attach(Errno::EINVAL))?;
+    #[allow(unsafe_code)]
+    // SAFETY:
+    // `cred` and `len` outlive the call and `len` is the size of `cred`, which the kernel writes at most
+    let result = unsafe {
+        libc::getsockopt(
+            socket.as_raw_fd(),
+            SOL_SOCKET,
+            SO_PEERCRED,
+            ptr::from_mut(&mut cred).cast(),
+            &mut len,
+        )
+    };
+    Errno::result(result)?;
+    Ok(cred)
+}
+
+/// The pids of process `pid` in the PID namespaces from the one of the daemon down to its own
+fn nspid(pid: u32) -> Result<Vec<i32>, Error> {
+    Ok(status(pid)?.nspid.unwrap_or_default())
+}
+
+/// Identifies the user namespace of process `pid` by the device and inode of `/proc/<pid>/ns/user`
+fn user_namespace(pid: u32) -> Result<(u64, u64), Error> {
+    let namespace = metadata(format!("/proc/{pid}/ns/user"))
+        .context("Could not inspect the user namespace of the process")
+        .map_err(attach(Errno::ESRCH))?;
+    Ok((namespace.dev(), namespace.ino()))
+}
@@ -0,0 +1,175 @@
+use nix::errno::Errno;
+use nix::libc::{gid_t, uid_t};
+
+use super::{Notification, Outcome};
+use crate::identity::{Identity, Ids};
+use crate::mem::RemoteSlice;
+use crate::Error;
+
+/// Returns the emulated real user id, the legacy 16-bit variant of the compat ABIs is left to the kernel
+#[subuidless::syscall]
+fn getuid(notification: &Notification) -> Result<Outcome, Error> {
+    if !notification.is_native() {
+        return Ok(Outcome::Continue);
+    }
+    getuid32(notification)
+}
+
+/// Returns the emulated real user id
+#[subuidless::syscall]
+fn getuid32(notification: &Notification) -> Result<Outcome, Error> {
+    get(notification, |identity| identity.uid.real)
+}
+
+/// Returns the emulated effective user id, the legacy 16-bit variant of the compat ABIs is left to the kernel
+#[subuidless::syscall]
+fn geteuid(notification: &Notification) -> Result<Outcome, Error> {
+    if !notification.is_native() {
+        return Ok(Outcome::Continue);
+    }
+    geteuid32(notification)
+}
+
+/// Returns the emulated effective user id
+#[subuidless::syscall]
+fn geteuid32(notification: &Notification) -> Result<Outcome, Error> {
+    get(notification, |identity| identity.uid.effective)
+}
+
+/// Returns the emulated real group id, the legacy 16-bit variant of the compat ABIs is left to the kernel
+#[subuidless::syscall]
+fn getgid(notification: &Notification) -> Result<Outcome, Error> {
+    if !notification.is_native() {
+        return Ok(Outcome::Continue);
+    }
+    getgid32(notification)
+}
+
+/// Returns the emulated real group id
+#[subuidless::syscall]
+fn getgid32(notification: &Notification) -> Result<Outcome, Error> {
+    get(notification, |identity| identity.gid.real)
+}
+
+/// Returns the emulated effective group id, the legacy 16-bit variant of the compat ABIs is left to the kernel
+#[subuidless::syscall]
+fn getegid(notification: &Notification) -> Result<Outcome, Error> {
+    if !notification.is_native() {
+        return Ok(Outcome::Continue);
+    }
+    getegid32(notification)
+}
+
+/// Returns the emulated effective group id
+#[subuidless::syscall]
+fn getegid32(notification: &Notification) -> Result<Outcome, Error> {
+    get(notification, |identity| identity.gid.effective)
+}
+
+/// Returns the emulated real, effective and saved user ids, the legacy 16-bit variant of the compat ABIs is left to the kernel
+#[subuidless::syscall]
+fn getresuid(
+    notification: &Notification,
+    #[out] ruid: &mut uid_t,
+    #[out] euid: &mut uid_t,
+    #[out] suid: &mut uid_t,
+) -> Result<Outcome, Error> {
+    if !notification.is_native() {
+        return Ok(Outcome::Continue);
+    }
+    getresuid32(notification, ruid, euid, suid)
+}
+
+/// Returns the emulated real, effective and saved user ids
+#[subuidless::syscall]
+fn getresuid32(
+    notification: &Notification,
+    #[out] ruid: &mut uid_t,
+    #[out] euid: &mut uid_t,
+    #[out] suid: &mut uid_t,
+) -> Result<Outcome, Error> {
+    get_res(notification, |identity| identity.uid, [ruid, euid, suid])
+}
+
+/// Returns the emulated real, effective and saved group ids, the legacy 16-bit variant of the compat ABIs is left to the kernel
+#[subuidless::syscall]
+fn getresgid(
+    notification: &Notification,
+    #[out] rgid: &mut gid_t,
+    #[out] egid: &mut gid_t,
+    #[out] sgid: &mut gid_t,
+) -> Result<Outcome, Error> {
+    if !notification.is_native() {
+        return Ok(Outcome::Continue);
+    }
+    getresgid32(notification, rgid, egid, sgid)
+}
+
+/// Returns the emulated real, effective and saved group ids
+#[subuidless::syscall]
+fn getresgid32(
+    notification: &Notification,
+    #[out] rgid: &mut gid_t,
+    #[out] egid: &mut gid_t,
+    #[out] sgid: &mut gid_t,
+) -> Result<Outcome, Error> {
+    get_res(notification, |identity| identity.gid, [rgid, egid, sgid])
+}
+
+/// Returns the emulated supplementary groups, the legacy 16-bit variant of the compat ABIs is left to the kernel
+#[subuidless::syscall]
+fn getgroups(
+    notification: &Notification,
+    size: i32,
+    list: RemoteSlice<gid_t>,
+) -> Result<Outcome, Error> {
+    if !notification.is_native() {
+        return Ok(Outcome::Continue);
+    }
+    getgroups32(notification, size, list)
+}
+
+/// Returns the emulated supplementary groups
+#[subuidless::syscall]
+fn getgroups32(
+    notification: &Notification,
+    size: i32,
+    list: RemoteSlice<gid_t>,
+) -> Result<Outcome, Error> {
+    let Some(identity) = Identity::tracked(notification.req.pid)? else {
+        return Ok(Outcome::Continue);
+    };
+    let len = usize::try_from(size).map_err(|_err| Errno::EINVAL)?;
+    // A size of zero only asks for the number of groups
+    if len != 0 {
+        list.with_len(len).write(&identity.groups)?;
+    }
+    Ok(Outcome::Return(
+        i64::try_from(identity.groups.len()).unwrap_or(i64::MAX),
+    ))
+}
+
+/// Returns the id `select` picks from the emulated credentials, the kernel answers for processes that never changed them
+fn get<F: FnOnce(&Identity) -> u32>(
+    notification: &Notification,
+    select: F,
+) -> Result<Outcome, Error> {
+    Ok(match Identity::tracked(notification.req.pid)? {
+        Some(identity) => Outcome::Return(select(&identity).into()),
+        None => Outcome::Continue,
+    })
+}
+
+/// Fills the real, effective and saved id of the ids `select` picks from the emulated credentials
+fn get_res<F: FnOnce(&Identity) -> Ids>(
+    notification: &Notification,
+    select: F,
+    [real, effective, saved]: [&mut u32; 3],
+) -> Result<Outcome, Error> {
+    let Some(identity) = Identity::tracked(notification.req.pid)? else {
+        return Ok(Outcome::Continue);
+    };
+    let ids = select(&identity);
+    (*real, *effective, *saved) = (ids.real, ids.effective, ids.saved);
+    Ok(Outcome::Return(0))
+}
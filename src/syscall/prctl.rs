@@ -0,0 +1,41 @@
+use nix::errno::Errno;
+use nix::libc::{PR_GET_KEEPCAPS, PR_GET_SECUREBITS, PR_SET_KEEPCAPS, PR_SET_SECUREBITS};
+
+use super::{Notification, Outcome};
+use crate::creds::SECBIT_KEEP_CAPS;
+use crate::identity::Identity;
+use crate::Error;
+
+/// Emulates the securebits of `PR_SET_KEEPCAPS` and `PR_SET_SECUREBITS` and reports them for the `PR_GET_*` options,
+/// so capability-aware programs keep their permitted capabilities across `setuid(2)`.
+/// Other options are left to the kernel, which also answers for processes that never changed their credentials.
+#[subuidless::syscall]
+fn prctl(notification: &Notification, option: i32, arg2: usize) -> Result<Outcome, Error> {
+    let pid = notification.req.pid;
+    match option {
+        PR_GET_KEEPCAPS | PR_GET_SECUREBITS => {
+            let Some(identity) = Identity::tracked(pid)? else {
+                return Ok(Outcome::Continue);
+            };
+            Ok(Outcome::Return(if option == PR_GET_KEEPCAPS {
+                i64::from(identity.securebits & SECBIT_KEEP_CAPS != 0)
+            } else {
+                i64::from(identity.securebits)
+            }))
+        }
+        PR_SET_KEEPCAPS => {
+            if arg2 > 1 {
+                return Err(Errno::EINVAL.into());
+            }
+            Identity::update(pid, |identity| Ok(identity.set_keepcaps(arg2 == 1)?))?;
+            Ok(Outcome::Return(0))
+        }
+        PR_SET_SECUREBITS => {
+            Identity::update(pid, |identity| {
+                Ok(identity.set_securebits(u64::try_from(arg2).unwrap_or(u64::MAX))?)
+            })?;
+            Ok(Outcome::Return(0))
+        }
+        _ => Ok(Outcome::Continue),
+    }
+}
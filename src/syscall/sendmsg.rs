@@ -0,0 +1,725 @@
+use std::mem::{size_of, zeroed};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread::Builder;
+
+use anyhow::{anyhow, Context};
+
+use libseccomp::ScmpFd;
+use nix::errno::Errno;
+use nix::fcntl::OFlag;
+use nix::libc::{
+    self, c_long, iovec, msghdr, sockaddr_storage, sockaddr_un, ucred, MSG_CMSG_CLOEXEC,
+    MSG_CTRUNC, MSG_DONTWAIT, MSG_NOSIGNAL, SCM_CREDENTIALS, SCM_RIGHTS, SOL_SOCKET,
+};
+use nix::sched::CloneFlags;
+use rustix::event::{poll, PollFd, PollFlags};
+use rustix::fs::{fcntl_getfl, OFlags};
+use rustix::io as rio;
+use rustix::net::sockopt::{get_socket_domain, get_socket_passcred};
+use rustix::net::AddressFamily;
+
+use super::getsockopt::{translate_ucred, PeerIds};
+use super::{Notification, Outcome};
+use crate::creds::{CAP_SETGID, CAP_SETUID};
+use crate::error::attach;
+use crate::identity::Identity;
+use crate::idmap::IdMap;
+use crate::mem::{Msghdr, RemoteBuf, RemoteMsghdr};
+use crate::nsenter::in_namespace;
+use crate::pidns::status;
+use crate::Error;
+
+/// `UIO_MAXIOV`: the largest number of `struct iovec` the kernel accepts
+const UIO_MAXIOV: usize = 1024;
+/// `MAX_RW_COUNT`: the most bytes a single transfer moves
+const MAX_RW_COUNT: usize = 0x7fff_f000;
+/// Control messages that are inspected at most, larger ones are left to the kernel
+const CONTROL_MAX: usize = 0x2_0000;
+/// Milliseconds after which a blocking transfer checks whether the caller still waits
+const POLL_INTERVAL: i32 = 100;
+/// Blocking transfers that wait on their own thread at most, further ones fail with `EAGAIN`
+const MAX_BLOCKED: usize = 64;
+
+/// Number of threads waiting for a blocking transfer
+static BLOCKED: AtomicUsize = AtomicUsize::new(0);
+
+#[allow(unsafe_code)]
+mod ioctl {
+    use nix::ioctl_write_ptr;
+
+    ioctl_write_ptr!(
+        /// `SECCOMP_IOCTL_NOTIF_ID_VALID`, see `seccomp_unotify(2)`
+        notif_id_valid,
+        b'!',
+        2,
+        u64
+    );
+}
+
+/// Sends messages with `SCM_CREDENTIALS` in place of the caller on a duplicate of its socket,
+/// so the kernel accepts the emulated ids of the caller and passes on its real ids instead.
+/// The message is sent from a child in the user and PID namespaces of the caller, where the kernel accepts its pid and real ids.
+/// Credentials with the pid of another process and messages without credentials are left to the kernel.
+/// A failing send does not raise `SIGPIPE` in the caller, a send timeout of the socket is not applied.
+#[subuidless::syscall]
+fn sendmsg(
+    notification: &Notification,
+    #[fd] sockfd: RawFd,
+    msg: RemoteMsghdr,
+    flags: i32,
+) -> Result<Outcome, Error> {
+    let msghdr = msg.read()?;
+    if msghdr.control == 0 || msghdr.controllen > CONTROL_MAX {
+        return Ok(Outcome::Continue);
+    }
+    let pid = notification.req.pid;
+    let Some(identity) = Identity::tracked(pid)? else {
+        return Ok(Outcome::Continue);
+    };
+    // The kernel rejects malformed control messages itself
+    let Some(mut messages) = parse_control(&msg.control(&msghdr).read()?, msg.word_size()) else {
+        return Ok(Outcome::Continue);
+    };
+    if !messages.iter().any(is_credentials) {
+        return Ok(Outcome::Continue);
+    }
+
+    let status = status(pid)?;
+    let own_pid = status
+        .nstgid
+        .as_ref()
+        .and_then(|nstgid| nstgid.last().copied())
+        .unwrap_or(status.tgid);
+    let uid_map = IdMap::read(format!("/proc/{pid}/uid_map")).map_err(attach(Errno::ESRCH))?;
+    let gid_map = IdMap::read(format!("/proc/{pid}/gid_map")).map_err(attach(Errno::ESRCH))?;
+    let (Some(real_uid), Some(real_gid)) = (
+        uid_map.to_inside(status.ruid),
+        gid_map.to_inside(status.rgid),
+    ) else {
+        return Ok(Outcome::Continue);
+    };
+    let mut fds = Vec::new();
+    for message in &mut messages {
+        if message.level == SOL_SOCKET && message.kind == SCM_RIGHTS {
+            // The descriptors are sent from the daemon, they stay open until the message was sent
+            let duplicates = take_caller_fds(notification, &message.data)?;
+            #[allow(clippy::host_endian_bytes)] // Read by the kernel of the daemon
+            let data = duplicates
+                .iter()
+                .flat_map(|fd| fd.as_raw_fd().to_ne_bytes())
+                .collect();
+            message.data = data;
+            fds.extend(duplicates);
+            continue;
+        }
+        if !is_credentials(message) {
+            continue;
+        }
+        let Some(cred) = read_ucred(&message.data) else {
+            return Ok(Outcome::Continue);
+        };
+        if cred.pid != own_pid {
+            return Ok(Outcome::Continue);
+        }
+        // The rules of `scm_check_creds` with the emulated ids
+        let uid_allowed = identity.uid.contains(cred.uid) || identity.has_capability(CAP_SETUID);
+        let gid_allowed = identity.gid.contains(cred.gid) || identity.has_capability(CAP_SETGID);
+        if !uid_allowed || !gid_allowed {
+            return Err(Errno::EPERM.into());
+        }
+        // The kernel reads the credentials in the namespaces of the child, which are the ones of the caller
+        message.data = ucred_bytes(&ucred {
+            pid: own_pid,
+            uid: real_uid,
+            gid: real_gid,
+        });
+    }
+    let mut control = Vec::new();
+    for message in &messages {
+        push_control(&mut control, message, size_of::<usize>());
+    }
+
+    let data = read_data(&msg, &msghdr)?;
+    let name = if msghdr.name == 0 {
+        Vec::new()
+    } else if usize::try_from(msghdr.namelen).unwrap_or(usize::MAX) > size_of::<sockaddr_storage>()
+    {
+        return Err(Errno::EINVAL.into());
+    } else {
+        msg.name(&msghdr).read()?
+    };
+    let socket = notification.get_fd(sockfd)?;
+    let blocking = is_blocking(&socket, flags)?;
+    in_background(notification, blocking, move |notification| {
+        send(
+            notification,
+            &socket,
+            &data,
+            &name,
+            &control,
+            flags,
+            blocking,
+        )
+    })
+}
+
+/// Sends `data` with the socket address `name` and the native `control` messages on `socket` with the `flags` of the caller,
+/// from a child in the user and PID namespaces of the caller. The send is left to the kernel if the child could not join them.
+fn send(
+    notification: &Notification,
+    socket: &OwnedFd,
+    data: &[u8],
+    name: &[u8],
+    control: &[u8],
+    flags: i32,
+    blocking: bool,
+) -> Result<Outcome, Error> {
+    let flags = flags | MSG_DONTWAIT | MSG_NOSIGNAL;
+    let (notify_fd, id) = (notification.fd, notification.req.id);
+    let sent = in_namespace(
+        notification.req.pid,
+        CloneFlags::CLONE_NEWPID,
+        0_u8,
+        |_| match send_all(notify_fd, id, socket, data, name, control, flags, blocking) {
+            Ok(sent) => c_long::try_from(sent).unwrap_or(c_long::MAX),
+            Err(errno) => {
+                errno.set();
+                -1
+            }
+        },
+    );
+    match sent {
+        Ok((sent, _)) => Ok(Outcome::Return(sent?)),
+        Err(_err) => Ok(Outcome::Continue),
+    }
+}
+
+/// Sends `data` in the child of `send`, so it only issues syscalls.
+/// Like the kernel, a blocking send on a stream socket continues until all data was sent, only the first part carries the control messages.
+#[allow(clippy::too_many_arguments)] // The parts of the message are kept in the buffers of the daemon
+fn send_all(
+    notify_fd: ScmpFd,
+    id: u64,
+    socket: &OwnedFd,
+    data: &[u8],
+    name: &[u8],
+    control: &[u8],
+    flags: i32,
+    blocking: bool,
+) -> Result<usize, Errno> {
+    let mut sent = wait_ready(notify_fd, id, socket, PollFlags::OUT, blocking, || {
+        send_once(socket, data, name, control, flags)
+    })?;
+    while blocking && sent < data.len() {
+        let rest = data.get(sent..).unwrap_or_default();
+        let result = wait_ready(notify_fd, id, socket, PollFlags::OUT, blocking, || {
+            send_once(socket, rest, &[], &[], flags)
+        });
+        match result {
+            Ok(0) => break,
+            Ok(len) => sent = sent.saturating_add(len),
+            // The part that was sent is reported, like the kernel does once a stream socket fails
+            Err(_err) => break,
+        }
+    }
+    Ok(sent)
+}
+
+/// Receives the message in place of the caller if the Unix socket passes credentials,
+/// so `SCM_CREDENTIALS` carry the emulated real ids of the sender.
+/// Descriptors of `SCM_RIGHTS` are installed in the caller, a receive timeout of the socket is not applied.
+#[subuidless::syscall]
+fn recvmsg(
+    notification: &Notification,
+    #[fd] sockfd: RawFd,
+    msg: RemoteMsghdr,
+    flags: i32,
+) -> Result<Outcome, Error> {
+    let socket = notification.get_fd(sockfd)?;
+    if !passes_credentials(&socket) {
+        return Ok(Outcome::Continue);
+    }
+    let blocking = is_blocking(&socket, flags)?;
+    in_background(notification, blocking, move |notification| {
+        receive(notification, &socket, &msg, flags, blocking)
+    })
+}
+
+/// Receives a message on `socket` for the caller and writes it into the buffers of `msg`
+fn receive(
+    notification: &Notification,
+    socket: &OwnedFd,
+    msg: &RemoteMsghdr,
+    flags: i32,
+    blocking: bool,
+) -> Result<Outcome, Error> {
+    let mut msghdr = msg.read()?;
+    if msghdr.iovlen > UIO_MAXIOV {
+        return Err(Errno::EMSGSIZE.into());
+    }
+    let iov = msg.iov(&msghdr)?;
+    let size = iov
+        .iter()
+        .map(RemoteBuf::len)
+        .fold(0, usize::saturating_add)
+        .min(MAX_RW_COUNT);
+
+    let native = size_of::<usize>();
+    let mut data = vec![0_u8; size];
+    let mut name = vec![0_u8; size_of::<sockaddr_un>()];
+    // Native control messages take up to twice the space of compat ones, the credentials come on top
+    let mut control = vec![
+        0_u8;
+        msghdr
+            .controllen
+            .min(CONTROL_MAX)
+            .saturating_mul(2)
+            .saturating_add(align(
+                header_len(native).saturating_add(size_of::<ucred>()),
+                native
+            ))
+    ];
+    let received = retry(notification, socket, PollFlags::IN, blocking, || {
+        receive_once(
+            socket,
+            &mut data,
+            &mut name,
+            &mut control,
+            flags | MSG_DONTWAIT | MSG_CMSG_CLOEXEC,
+        )
+    })?;
+
+    let word = msg.word_size();
+    let cloexec = if flags & MSG_CMSG_CLOEXEC == 0_i32 {
+        OFlag::empty()
+    } else {
+        OFlag::O_CLOEXEC
+    };
+    let limit = if msghdr.control == 0 {
+        0
+    } else {
+        msghdr.controllen
+    };
+    let (caller_control, truncated) = forward_control(
+        notification,
+        control.get(..received.controllen).unwrap_or_default(),
+        word,
+        limit,
+        cloexec,
+    )?;
+    // `MSG_CMSG_CLOEXEC` is reported back only if the caller passed it
+    msghdr.flags = (received.flags & !MSG_CMSG_CLOEXEC) | (flags & MSG_CMSG_CLOEXEC);
+    if truncated {
+        msghdr.flags |= MSG_CTRUNC;
+    }
+
+    let mut remaining = data.get(..received.len.min(data.len())).unwrap_or_default();
+    for buf in &iov {
+        let (chunk, rest) = remaining.split_at(buf.len().min(remaining.len()));
+        if !chunk.is_empty() {
+            buf.write(chunk)?;
+        }
+        remaining = rest;
+    }
+    if msghdr.name != 0 {
+        let len = usize::try_from(received.namelen.min(msghdr.namelen)).unwrap_or_default();
+        msg.name(&msghdr)
+            .write(name.get(..len).unwrap_or_default())?;
+        msghdr.namelen = received.namelen;
+    }
+    if !caller_control.is_empty() {
+        msg.control(&msghdr).write(&caller_control)?;
+    }
+    msghdr.controllen = caller_control.len();
+    msg.write_received(&msghdr)?;
+    Ok(Outcome::Return(
+        i64::try_from(received.len).map_err(attach(Errno::EOVERFLOW))?,
+    ))
+}
+
+/// Translates the native control messages the daemon received into the ones for the caller, with the layout of `word` sized `size_t`.
+/// Credentials are the emulated ones and descriptors are installed in the caller with `cloexec`.
+/// Messages that do not fit into `limit` bytes are dropped, which the returned flag reports.
+fn forward_control(
+    notification: &Notification,
+    control: &[u8],
+    word: usize,
+    limit: usize,
+    cloexec: OFlag,
+) -> Result<(Vec<u8>, bool), Error> {
+    // Received descriptors are owned right away, so they are closed if they do not reach the caller
+    let messages: Vec<(ControlMessage, Vec<OwnedFd>)> = parse_control(control, size_of::<usize>())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|message| {
+            let fds = if message.level == SOL_SOCKET && message.kind == SCM_RIGHTS {
+                take_fds(&message.data)
+            } else {
+                Vec::new()
+            };
+            (message, fds)
+        })
+        .collect();
+
+    let mut forwarded = Vec::new();
+    let mut truncated = false;
+    for (mut message, fds) in messages {
+        if forwarded
+            .len()
+            .saturating_add(header_len(word))
+            .saturating_add(message.data.len())
+            > limit
+        {
+            truncated = true;
+            break;
+        }
+        if is_credentials(&message) {
+            if let Some(cred) = read_ucred(&message.data) {
+                // Credentials a sender passed explicitly keep the ids it chose
+                let ids = if has_real_ids(&cred) {
+                    PeerIds::Real
+                } else {
+                    PeerIds::Kernel
+                };
+                message.data = ucred_bytes(&translate_ucred(notification.req.pid, cred, ids)?);
+            }
+        }
+        if !fds.is_empty() {
+            message.data = fds
+                .iter()
+                .map(|fd| notification.add_fd(fd, cloexec))
+                .collect::<Result<Vec<RawFd>, Error>>()?
+                .iter()
+                .flat_map(|fd| fd.to_ne_bytes())
+                .collect();
+        }
+        push_control(&mut forwarded, &message, word);
+    }
+    forwarded.truncate(limit);
+    Ok((forwarded, truncated))
+}
+
+/// A control message of `sendmsg` and `recvmsg`
+struct ControlMessage {
+    /// `cmsg_level`
+    level: i32,
+    /// `cmsg_type`
+    kind: i32,
+    /// The data following the header
+    data: Vec<u8>,
+}
+
+/// What the daemon received in place of the caller
+struct Received {
+    /// Size of the message
+    len: usize,
+    /// Size of the socket address of the sender
+    namelen: u32,
+    /// Size of the control messages
+    controllen: usize,
+    /// Flags of the message
+    flags: i32,
+}
+
+/// Whether `socket` is a Unix socket that receives the credentials of the sender with `SO_PASSCRED`
+fn passes_credentials(socket: &OwnedFd) -> bool {
+    get_socket_domain(socket).is_ok_and(|domain| domain == AddressFamily::UNIX)
+        && get_socket_passcred(socket).unwrap_or(false)
+}
+
+/// Whether a transfer on `socket` with the `flags` of the caller waits until the socket is ready
+fn is_blocking(socket: &OwnedFd, flags: i32) -> Result<bool, Error> {
+    Ok(flags & MSG_DONTWAIT == 0_i32 && !fcntl_getfl(socket)?.contains(OFlags::NONBLOCK))
+}
+
+/// Runs `handler` for the notification, a `blocking` one on its own thread which answers the notification once the transfer is done.
+/// Otherwise a sender and a receiver blocked on each other could take up all workers.
+/// At most `MAX_BLOCKED` transfers wait at the same time, so a container can not spawn any number of threads in the daemon.
+fn in_background<F>(
+    notification: &Notification,
+    blocking: bool,
+    handler: F,
+) -> Result<Outcome, Error>
+where
+    F: FnOnce(&Notification) -> Result<Outcome, Error> + Send + 'static,
+{
+    if !blocking {
+        return handler(notification);
+    }
+    BLOCKED
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |blocked| {
+            blocked
+                .checked_add(1)
+                .filter(|&blocked| blocked <= MAX_BLOCKED)
+        })
+        .map_err(|_blocked| anyhow!("Too many blocking transfers"))
+        .map_err(attach(Errno::EAGAIN))?;
+    let waiting = notification.clone();
+    let spawned = Builder::new()
+        .name(format!("blocked-{}", notification.req.id))
+        .spawn(move || {
+            let result = catch_unwind(AssertUnwindSafe(|| handler(&waiting)))
+                .map_err(|_panic| anyhow!("Handler of a blocking transfer panicked"))
+                .map_err(attach(Errno::EIO))
+                .and_then(|result| result);
+            waiting.respond(result);
+            BLOCKED.fetch_sub(1, Ordering::SeqCst);
+        });
+    if spawned.is_err() {
+        BLOCKED.fetch_sub(1, Ordering::SeqCst);
+    }
+    spawned
+        .context("Could not spawn a thread for a blocking transfer")
+        .map_err(attach(Errno::EAGAIN))?;
+    Ok(Outcome::Responded)
+}
+
+/// Repeats the non-blocking `transfer` on `socket` until it does not fail with `EAGAIN`, waiting for `events` in between if `blocking`.
+/// While waiting the notification is checked, so a caller that was interrupted or killed does not keep the thread busy.
+fn retry<T, F: FnMut() -> Result<T, Errno>>(
+    notification: &Notification,
+    socket: &OwnedFd,
+    events: PollFlags,
+    blocking: bool,
+    transfer: F,
+) -> Result<T, Error> {
+    Ok(wait_ready(
+        notification.fd,
+        notification.req.id,
+        socket,
+        events,
+        blocking,
+        transfer,
+    )?)
+}
+
+/// `retry` for the notification `id` received on `notify_fd`, which only issues syscalls
+fn wait_ready<T, F: FnMut() -> Result<T, Errno>>(
+    notify_fd: ScmpFd,
+    id: u64,
+    socket: &OwnedFd,
+    events: PollFlags,
+    blocking: bool,
+    mut transfer: F,
+) -> Result<T, Errno> {
+    loop {
+        match transfer() {
+            Err(Errno::EAGAIN) if blocking => {
+                match poll(&mut [PollFd::new(socket, events)], POLL_INTERVAL) {
+                    Ok(_) | Err(rio::Errno::INTR) => {}
+                    Err(err) => return Err(Errno::from_raw(err.raw_os_error())),
+                }
+                #[allow(unsafe_code)]
+                // SAFETY:
+                // `id` outlives the call, the kernel only reads it
+                let valid = unsafe { ioctl::notif_id_valid(notify_fd, &id) };
+                valid.map_err(|_err| Errno::EINTR)?;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Reads the data of the `struct iovec` array of `msghdr`, at most `MAX_RW_COUNT` bytes like the kernel sends
+fn read_data(msg: &RemoteMsghdr, msghdr: &Msghdr) -> Result<Vec<u8>, Error> {
+    if msghdr.iovlen > UIO_MAXIOV {
+        return Err(Errno::EMSGSIZE.into());
+    }
+    let mut data = Vec::new();
+    for buf in msg.iov(msghdr)? {
+        let len = buf.len().min(MAX_RW_COUNT.saturating_sub(data.len()));
+        if len != 0 {
+            data.extend(buf.with_len(len).read()?);
+        }
+    }
+    Ok(data)
+}
+
+/// `sendmsg(2)` from the buffers of the daemon, without a socket address if `name` is empty
+fn send_once(
+    socket: &OwnedFd,
+    data: &[u8],
+    name: &[u8],
+    control: &[u8],
+    flags: i32,
+) -> Result<usize, Errno> {
+    let mut iov = iovec {
+        iov_base: data.as_ptr().cast_mut().cast(),
+        iov_len: data.len(),
+    };
+    #[allow(unsafe_code)]
+    // SAFETY:
+    // `msghdr` consists of integers and raw pointers, for which all zeroes are valid
+    let mut header: msghdr = unsafe { zeroed() };
+    if !name.is_empty() {
+        header.msg_name = name.as_ptr().cast_mut().cast();
+        header.msg_namelen = u32::try_from(name.len()).map_err(|_err| Errno::EINVAL)?;
+    }
+    header.msg_iov = &mut iov;
+    header.msg_iovlen = 1;
+    if !control.is_empty() {
+        header.msg_control = control.as_ptr().cast_mut().cast();
+        header.msg_controllen = control.len();
+    }
+
+    #[allow(unsafe_code)]
+    // SAFETY:
+    // The pointers of `header` and `iov` refer to buffers that outlive the call and have the sizes stored next to them,
+    // the kernel only reads them for a send
+    let len = unsafe { libc::sendmsg(socket.as_raw_fd(), &header, flags) };
+    usize::try_from(Errno::result(len)?).map_err(|_err| Errno::EINVAL)
+}
+
+/// `recvmsg(2)` into the buffers of the daemon
+fn receive_once(
+    socket: &OwnedFd,
+    data: &mut [u8],
+    name: &mut [u8],
+    control: &mut [u8],
+    flags: i32,
+) -> Result<Received, Errno> {
+    let mut iov = iovec {
+        iov_base: data.as_mut_ptr().cast(),
+        iov_len: data.len(),
+    };
+    #[allow(unsafe_code)]
+    // SAFETY:
+    // `msghdr` consists of integers and raw pointers, for which all zeroes are valid
+    let mut header: msghdr = unsafe { zeroed() };
+    header.msg_name = name.as_mut_ptr().cast();
+    header.msg_namelen = u32::try_from(name.len()).map_err(|_err| Errno::EINVAL)?;
+    header.msg_iov = &mut iov;
+    header.msg_iovlen = 1;
+    header.msg_control = control.as_mut_ptr().cast();
+    header.msg_controllen = control.len();
+
+    #[allow(unsafe_code)]
+    // SAFETY:
+    // The pointers of `header` and `iov` refer to buffers that outlive the call and have the sizes stored next to them
+    let len = unsafe { libc::recvmsg(socket.as_raw_fd(), &mut header, flags) };
+    Ok(Received {
+        len: usize::try_from(Errno::result(len)?).map_err(|_err| Errno::EINVAL)?,
+        namelen: header.msg_namelen,
+        controllen: header.msg_controllen,
+        flags: header.msg_flags,
+    })
+}
+
+/// Whether `message` is a `SCM_CREDENTIALS` message
+fn is_credentials(message: &ControlMessage) -> bool {
+    message.level == SOL_SOCKET && message.kind == SCM_CREDENTIALS
+}
+
+/// Whether `cred` carries the real ids of the sending process, which the kernel fills in if the sender passed no credentials
+fn has_real_ids(cred: &ucred) -> bool {
+    u32::try_from(cred.pid)
+        .ok()
+        .filter(|&pid| pid != 0)
+        .and_then(|pid| status(pid).ok())
+        .is_some_and(|status| status.ruid == cred.uid && status.rgid == cred.gid)
+}
+
+/// Duplicates the descriptors of the caller listed in the data of its `SCM_RIGHTS` message
+#[allow(clippy::host_endian_bytes)] // The caller shares the byte order of the daemon
+fn take_caller_fds(notification: &Notification, data: &[u8]) -> Result<Vec<OwnedFd>, Error> {
+    data.chunks_exact(size_of::<RawFd>())
+        .filter_map(|fd| Some(RawFd::from_ne_bytes(fd.try_into().ok()?)))
+        .map(|fd| notification.get_fd(fd).map_err(|_err| Errno::EBADF.into()))
+        .collect()
+}
+
+/// Size of the header of a control message in an ABI with `word` sized `size_t`, `CMSG_LEN(0)`
+fn header_len(word: usize) -> usize {
+    word.saturating_add(8)
+}
+
+/// `len` rounded up to the alignment of control messages in an ABI with `word` sized `size_t`, `CMSG_ALIGN`
+fn align(len: usize, word: usize) -> usize {
+    len.div_ceil(word).saturating_mul(word)
+}
+
+/// Parses the control messages in `control` of an ABI with `word` sized `size_t`, `None` if they are malformed
+#[allow(clippy::host_endian_bytes)] // The caller shares the byte order of the daemon
+fn parse_control(control: &[u8], word: usize) -> Option<Vec<ControlMessage>> {
+    let header = header_len(word);
+    let mut messages = Vec::new();
+    let mut rest = control;
+    while rest.len() >= header {
+        let len = if word == 4 {
+            usize::try_from(u32::from_ne_bytes(rest.get(..4)?.try_into().ok()?)).ok()?
+        } else {
+            usize::try_from(u64::from_ne_bytes(rest.get(..8)?.try_into().ok()?)).ok()?
+        };
+        let level = i32::from_ne_bytes(rest.get(word..word.saturating_add(4))?.try_into().ok()?);
+        let kind = i32::from_ne_bytes(rest.get(word.saturating_add(4)..header)?.try_into().ok()?);
+        messages.push(ControlMessage {
+            level,
+            kind,
+            data: rest.get(header..len)?.to_vec(),
+        });
+        rest = rest.get(align(len, word)..).unwrap_or_default();
+    }
+    Some(messages)
+}
+
+/// Appends `message` to `control` in the layout of an ABI with `word` sized `size_t`, including the padding
+#[allow(clippy::host_endian_bytes)] // The caller shares the byte order of the daemon
+fn push_control(control: &mut Vec<u8>, message: &ControlMessage, word: usize) {
+    let len = header_len(word).saturating_add(message.data.len());
+    if word == 4 {
+        control.extend_from_slice(&u32::try_from(len).unwrap_or(u32::MAX).to_ne_bytes());
+    } else {
+        control.extend_from_slice(&u64::try_from(len).unwrap_or(u64::MAX).to_ne_bytes());
+    }
+    control.extend_from_slice(&message.level.to_ne_bytes());
+    control.extend_from_slice(&message.kind.to_ne_bytes());
+    control.extend_from_slice(&message.data);
+    control.resize(
+        control
+            .len()
+            .saturating_add(align(len, word).saturating_sub(len)),
+        0,
+    );
+}
+
+/// The `ucred` of a `SCM_CREDENTIALS` message, `None` if the size does not match
+#[allow(clippy::host_endian_bytes)] // The caller shares the byte order of the daemon
+fn read_ucred(data: &[u8]) -> Option<ucred> {
+    if data.len() != size_of::<ucred>() {
+        return None;
+    }
+    Some(ucred {
+        pid: i32::from_ne_bytes(data.get(0..4)?.try_into().ok()?),
+        uid: u32::from_ne_bytes(data.get(4..8)?.try_into().ok()?),
+        gid: u32::from_ne_bytes(data.get(8..12)?.try_into().ok()?),
+    })
+}
+
+/// The data of a `SCM_CREDENTIALS` message
+#[allow(clippy::host_endian_bytes)] // The caller shares the byte order of the daemon
+fn ucred_bytes(cred: &ucred) -> Vec<u8> {
+    [
+        cred.pid.to_ne_bytes(),
+        cred.uid.to_ne_bytes(),
+        cred.gid.to_ne_bytes(),
+    ]
+    .concat()
+}
+
+/// Takes ownership of the descriptors of a `SCM_RIGHTS` message the daemon received
+#[allow(clippy::host_endian_bytes)] // The descriptors are in the byte order of the daemon
+fn take_fds(data: &[u8]) -> Vec<OwnedFd> {
+    data.chunks_exact(size_of::<RawFd>())
+        .filter_map(|fd| Some(RawFd::from_ne_bytes(fd.try_into().ok()?)))
+        .map(|fd| {
+            #[allow(unsafe_code)]
+            // SAFETY:
+            // The kernel installed the descriptor in the daemon for this message, nothing else owns it
+            unsafe {
+                OwnedFd::from_raw_fd(fd)
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,232 @@
+//! The `set*id` syscalls change the emulated credentials of the caller, the kernel keeps the real ones.
+//! The legacy 16-bit variants of the 32-bit compat ABIs are left to the kernel.
+use nix::errno::Errno;
+use nix::libc::{gid_t, uid_t};
+
+use super::{Notification, Outcome};
+use crate::creds::CAP_SETGID;
+use crate::identity::Identity;
+use crate::mem::RemoteSlice;
+use crate::Error;
+
+/// Maximum number of supplementary groups, `NGROUPS_MAX` of the kernel
+const NGROUPS_MAX: usize = 0x1_0000;
+
+/// Sets the emulated user ids, the legacy 16-bit variant of the compat ABIs is left to the kernel
+#[subuidless::syscall]
+fn setuid(notification: &Notification, uid: uid_t) -> Result<Outcome, Error> {
+    if !notification.is_native() {
+        return Ok(Outcome::Continue);
+    }
+    Ok(Outcome::Return(setuid32(notification, uid)?))
+}
+
+/// Sets the emulated user ids
+#[subuidless::syscall]
+fn setuid32(notification: &Notification, uid: uid_t) -> Result<i64, Error> {
+    update(notification, |identity| Ok(identity.setuid(uid)?))?;
+    Ok(0)
+}
+
+/// Sets the emulated group ids, the legacy 16-bit variant of the compat ABIs is left to the kernel
+#[subuidless::syscall]
+fn setgid(notification: &Notification, gid: gid_t) -> Result<Outcome, Error> {
+    if !notification.is_native() {
+        return Ok(Outcome::Continue);
+    }
+    Ok(Outcome::Return(setgid32(notification, gid)?))
+}
+
+/// Sets the emulated group ids
+#[subuidless::syscall]
+fn setgid32(notification: &Notification, gid: gid_t) -> Result<i64, Error> {
+    update(notification, |identity| Ok(identity.setgid(gid)?))?;
+    Ok(0)
+}
+
+/// Sets the emulated real and effective user ids, the legacy 16-bit variant of the compat ABIs is left to the kernel
+#[subuidless::syscall]
+fn setreuid(
+    notification: &Notification,
+    ruid: Option<uid_t>,
+    euid: Option<uid_t>,
+) -> Result<Outcome, Error> {
+    if !notification.is_native() {
+        return Ok(Outcome::Continue);
+    }
+    Ok(Outcome::Return(setreuid32(notification, ruid, euid)?))
+}
+
+/// Sets the emulated real and effective user ids
+#[subuidless::syscall]
+fn setreuid32(
+    notification: &Notification,
+    ruid: Option<uid_t>,
+    euid: Option<uid_t>,
+) -> Result<i64, Error> {
+    update(notification, |identity| Ok(identity.setreuid(ruid, euid)?))?;
+    Ok(0)
+}
+
+/// Sets the emulated real and effective group ids, the legacy 16-bit variant of the compat ABIs is left to the kernel
+#[subuidless::syscall]
+fn setregid(
+    notification: &Notification,
+    rgid: Option<gid_t>,
+    egid: Option<gid_t>,
+) -> Result<Outcome, Error> {
+    if !notification.is_native() {
+        return Ok(Outcome::Continue);
+    }
+    Ok(Outcome::Return(setregid32(notification, rgid, egid)?))
+}
+
+/// Sets the emulated real and effective group ids
+#[subuidless::syscall]
+fn setregid32(
+    notification: &Notification,
+    rgid: Option<gid_t>,
+    egid: Option<gid_t>,
+) -> Result<i64, Error> {
+    update(notification, |identity| Ok(identity.setregid(rgid, egid)?))?;
+    Ok(0)
+}
+
+/// Sets the emulated real, effective and saved user ids, the legacy 16-bit variant of the compat ABIs is left to the kernel
+#[subuidless::syscall]
+fn setresuid(
+    notification: &Notification,
+    ruid: Option<uid_t>,
+    euid: Option<uid_t>,
+    suid: Option<uid_t>,
+) -> Result<Outcome, Error> {
+    if !notification.is_native() {
+        return Ok(Outcome::Continue);
+    }
+    Ok(Outcome::Return(setresuid32(
+        notification,
+        ruid,
+        euid,
+        suid,
+    )?))
+}
+
+/// Sets the emulated real, effective and saved user ids
+#[subuidless::syscall]
+fn setresuid32(
+    notification: &Notification,
+    ruid: Option<uid_t>,
+    euid: Option<uid_t>,
+    suid: Option<uid_t>,
+) -> Result<i64, Error> {
+    update(notification, |identity| {
+        Ok(identity.setresuid(ruid, euid, suid)?)
+    })?;
+    Ok(0)
+}
+
+/// Sets the emulated real, effective and saved group ids, the legacy 16-bit variant of the compat ABIs is left to the kernel
+#[subuidless::syscall]
+fn setresgid(
+    notification: &Notification,
+    rgid: Option<gid_t>,
+    egid: Option<gid_t>,
+    sgid: Option<gid_t>,
+) -> Result<Outcome, Error> {
+    if !notification.is_native() {
+        return Ok(Outcome::Continue);
+    }
+    Ok(Outcome::Return(setresgid32(
+        notification,
+        rgid,
+        egid,
+        sgid,
+    )?))
+}
+
+/// Sets the emulated real, effective and saved group ids
+#[subuidless::syscall]
+fn setresgid32(
+    notification: &Notification,
+    rgid: Option<gid_t>,
+    egid: Option<gid_t>,
+    sgid: Option<gid_t>,
+) -> Result<i64, Error> {
+    update(notification, |identity| {
+        Ok(identity.setresgid(rgid, egid, sgid)?)
+    })?;
+    Ok(0)
+}
+
+/// Sets the emulated filesystem user id and returns the previous one, the legacy 16-bit variant of the compat ABIs is left to the kernel
+#[subuidless::syscall]
+fn setfsuid(notification: &Notification, fsuid: uid_t) -> Result<Outcome, Error> {
+    if !notification.is_native() {
+        return Ok(Outcome::Continue);
+    }
+    Ok(Outcome::Return(setfsuid32(notification, fsuid)?))
+}
+
+/// Sets the emulated filesystem user id and returns the previous one
+#[subuidless::syscall]
+fn setfsuid32(notification: &Notification, fsuid: uid_t) -> Result<i64, Error> {
+    update(notification, |identity| Ok(identity.setfsuid(fsuid))).map(i64::from)
+}
+
+/// Sets the emulated filesystem group id and returns the previous one, the legacy 16-bit variant of the compat ABIs is left to the kernel
+#[subuidless::syscall]
+fn setfsgid(notification: &Notification, fsgid: gid_t) -> Result<Outcome, Error> {
+    if !notification.is_native() {
+        return Ok(Outcome::Continue);
+    }
+    Ok(Outcome::Return(setfsgid32(notification, fsgid)?))
+}
+
+/// Sets the emulated filesystem group id and returns the previous one
+#[subuidless::syscall]
+fn setfsgid32(notification: &Notification, fsgid: gid_t) -> Result<i64, Error> {
+    update(notification, |identity| Ok(identity.setfsgid(fsgid))).map(i64::from)
+}
+
+/// Sets the emulated supplementary groups, which requires `CAP_SETGID`, the legacy 16-bit variant of the compat ABIs is left to the kernel
+#[subuidless::syscall]
+fn setgroups(
+    notification: &Notification,
+    size: i32,
+    list: RemoteSlice<gid_t>,
+) -> Result<Outcome, Error> {
+    if !notification.is_native() {
+        return Ok(Outcome::Continue);
+    }
+    Ok(Outcome::Return(setgroups32(notification, size, list)?))
+}
+
+/// Sets the emulated supplementary groups, which requires `CAP_SETGID`
+#[subuidless::syscall]
+fn setgroups32(
+    notification: &Notification,
+    size: i32,
+    list: RemoteSlice<gid_t>,
+) -> Result<i64, Error> {
+    update(notification, |identity| {
+        if !identity.has_capability(CAP_SETGID) {
+            return Err(Errno::EPERM.into());
+        }
+        let len = usize::try_from(size)
+            .ok()
+            .filter(|&len| len <= NGROUPS_MAX)
+            .ok_or(Errno::EINVAL)?;
+        identity.groups = list.with_len(len).read()?;
+        Ok(())
+    })?;
+    Ok(0)
+}
+
+/// Applies `change` to the emulated credentials of the caller and tracks them from then on.
+/// The credentials are left unchanged if `change` fails.
+fn update<T, F: FnOnce(&mut Identity) -> Result<T, Error>>(
+    notification: &Notification,
+    change: F,
+) -> Result<T, Error> {
+    Identity::update(notification.req.pid, change)
+}
@@ -0,0 +1,438 @@
+use std::ffi::OsString;
+use std::os::fd::{AsRawFd, OwnedFd, RawFd};
+use std::path::{Path, PathBuf};
+
+use nix::errno::Errno;
+use nix::fcntl::AtFlags;
+use nix::libc::{uid_t, S_IFDIR, S_IFLNK, S_IFMT};
+use nix::sys::stat::{self, fstat, FchmodatFlags, Mode};
+use rustix::fs;
+use rustix::io as rio;
+
+use super::fstatat::emulated_ids;
+use super::{Notification, Outcome};
+use crate::acl::{Acl, ACL_GROUP, ACL_USER};
+use crate::creds::{Credentials, CAP_SETFCAP};
+use crate::error::attach;
+use crate::identity::FileCapabilities;
+use crate::idmap::{IdMap, OVERFLOW_ID};
+use crate::mem::RemoteBuf;
+use crate::resolve::{fd_path, resolve};
+use crate::xattr::{find_xa_value_fd, set_xa_value_fd, Emulated};
+use crate::Error;
+
+/// `XATTR_CREATE`: fail if the xAttribute exists
+const XATTR_CREATE: i32 = 1;
+/// `XATTR_REPLACE`: fail if the xAttribute does not exist
+const XATTR_REPLACE: i32 = 2;
+/// Revision of `struct vfs_cap_data` with 64 capabilities
+const VFS_CAP_REVISION_2: u32 = 0x0200_0000;
+/// Revision of `struct vfs_cap_data` with 64 capabilities and the root uid of the user namespace
+const VFS_CAP_REVISION_3: u32 = 0x0300_0000;
+/// Mask of the revision in `magic_etc`
+const VFS_CAP_REVISION_MASK: u32 = 0xff00_0000;
+/// Flag of `magic_etc` that makes the permitted capabilities effective
+const VFS_CAP_FLAGS_EFFECTIVE: u32 = 0x01;
+/// Size of a revision 2 `struct vfs_cap_data`
+const XATTR_CAPS_SZ_2: usize = 20;
+/// Size of a revision 3 `struct vfs_cap_data`
+const XATTR_CAPS_SZ_3: usize = 24;
+/// `XATTR_SIZE_MAX`: the largest value of an xAttribute
+const XATTR_SIZE_MAX: usize = 0x1_0000;
+
+/// Stores file capabilities and ACLs in the emulated ownership, other xAttributes are left to the kernel
+#[subuidless::syscall]
+fn setxattr(
+    notification: &Notification,
+    pathname: PathBuf,
+    name: OsString,
+    value: RemoteBuf,
+    size: usize,
+    flags: i32,
+) -> Result<Outcome, Error> {
+    let Some(xattr) = Emulated::from_name(&name) else {
+        return Ok(Outcome::Continue);
+    };
+    let file = resolve(notification, None, &pathname, AtFlags::empty())?;
+    set_value(notification, &file, xattr, &value.with_len(size), flags)
+}
+
+/// Stores file capabilities and ACLs in the emulated ownership, other xAttributes are left to the kernel
+#[subuidless::syscall]
+fn lsetxattr(
+    notification: &Notification,
+    pathname: PathBuf,
+    name: OsString,
+    value: RemoteBuf,
+    size: usize,
+    flags: i32,
+) -> Result<Outcome, Error> {
+    let Some(xattr) = Emulated::from_name(&name) else {
+        return Ok(Outcome::Continue);
+    };
+    let file = resolve(notification, None, &pathname, AtFlags::AT_SYMLINK_NOFOLLOW)?;
+    set_value(notification, &file, xattr, &value.with_len(size), flags)
+}
+
+/// Stores file capabilities and ACLs in the emulated ownership, other xAttributes are left to the kernel
+#[subuidless::syscall]
+fn fsetxattr(
+    notification: &Notification,
+    #[fd] fd: RawFd,
+    name: OsString,
+    value: RemoteBuf,
+    size: usize,
+    flags: i32,
+) -> Result<Outcome, Error> {
+    let Some(xattr) = Emulated::from_name(&name) else {
+        return Ok(Outcome::Continue);
+    };
+    let file = resolve_fd(notification, fd)?;
+    set_value(notification, &file, xattr, &value.with_len(size), flags)
+}
+
+/// Reports the emulated file capabilities and ACLs, other xAttributes are left to the kernel
+#[subuidless::syscall]
+fn getxattr(
+    notification: &Notification,
+    pathname: PathBuf,
+    name: OsString,
+    value: RemoteBuf,
+    size: usize,
+) -> Result<Outcome, Error> {
+    let Some(xattr) = Emulated::from_name(&name) else {
+        return Ok(Outcome::Continue);
+    };
+    let file = resolve(notification, None, &pathname, AtFlags::empty())?;
+    get_value(notification, &file, xattr, &value.with_len(size))
+}
+
+/// Reports the emulated file capabilities and ACLs, other xAttributes are left to the kernel
+#[subuidless::syscall]
+fn lgetxattr(
+    notification: &Notification,
+    pathname: PathBuf,
+    name: OsString,
+    value: RemoteBuf,
+    size: usize,
+) -> Result<Outcome, Error> {
+    let Some(xattr) = Emulated::from_name(&name) else {
+        return Ok(Outcome::Continue);
+    };
+    let file = resolve(notification, None, &pathname, AtFlags::AT_SYMLINK_NOFOLLOW)?;
+    get_value(notification, &file, xattr, &value.with_len(size))
+}
+
+/// Reports the emulated file capabilities and ACLs, other xAttributes are left to the kernel
+#[subuidless::syscall]
+fn fgetxattr(
+    notification: &Notification,
+    #[fd] fd: RawFd,
+    name: OsString,
+    value: RemoteBuf,
+    size: usize,
+) -> Result<Outcome, Error> {
+    let Some(xattr) = Emulated::from_name(&name) else {
+        return Ok(Outcome::Continue);
+    };
+    let file = resolve_fd(notification, fd)?;
+    get_value(notification, &file, xattr, &value.with_len(size))
+}
+
+/// Removes the emulated file capabilities and ACLs, other xAttributes are left to the kernel
+#[subuidless::syscall]
+fn removexattr(
+    notification: &Notification,
+    pathname: PathBuf,
+    name: OsString,
+) -> Result<Outcome, Error> {
+    let Some(xattr) = Emulated::from_name(&name) else {
+        return Ok(Outcome::Continue);
+    };
+    let file = resolve(notification, None, &pathname, AtFlags::empty())?;
+    remove_value(notification, &file, xattr)
+}
+
+/// Removes the emulated file capabilities and ACLs, other xAttributes are left to the kernel
+#[subuidless::syscall]
+fn lremovexattr(
+    notification: &Notification,
+    pathname: PathBuf,
+    name: OsString,
+) -> Result<Outcome, Error> {
+    let Some(xattr) = Emulated::from_name(&name) else {
+        return Ok(Outcome::Continue);
+    };
+    let file = resolve(notification, None, &pathname, AtFlags::AT_SYMLINK_NOFOLLOW)?;
+    remove_value(notification, &file, xattr)
+}
+
+/// Removes the emulated file capabilities and ACLs, other xAttributes are left to the kernel
+#[subuidless::syscall]
+fn fremovexattr(
+    notification: &Notification,
+    #[fd] fd: RawFd,
+    name: OsString,
+) -> Result<Outcome, Error> {
+    let Some(xattr) = Emulated::from_name(&name) else {
+        return Ok(Outcome::Continue);
+    };
+    let file = resolve_fd(notification, fd)?;
+    remove_value(notification, &file, xattr)
+}
+
+/// The file behind the descriptor `fd` of the caller
+fn resolve_fd(notification: &Notification, fd: RawFd) -> Result<OwnedFd, Error> {
+    resolve(
+        notification,
+        Some(fd),
+        Path::new(""),
+        AtFlags::AT_EMPTY_PATH,
+    )
+}
+
+/// Stores `value` as the emulated xAttribute `xattr` of `file`
+fn set_value(
+    notification: &Notification,
+    file: &OwnedFd,
+    xattr: Emulated,
+    value: &RemoteBuf,
+    flags: i32,
+) -> Result<Outcome, Error> {
+    if flags & !(XATTR_CREATE | XATTR_REPLACE) != 0_i32 {
+        return Err(Errno::EINVAL.into());
+    }
+    match xattr {
+        Emulated::Capability => set_capability(notification, file, value, flags),
+        Emulated::AclAccess | Emulated::AclDefault => {
+            set_acl(notification, file, xattr, value, flags)
+        }
+    }
+}
+
+/// Stores `value` as the capabilities of `file`.
+/// Like the kernel, capabilities for the root of the user namespace are stored as revision 2.
+fn set_capability(
+    notification: &Notification,
+    file: &OwnedFd,
+    value: &RemoteBuf,
+    flags: i32,
+) -> Result<Outcome, Error> {
+    if value.len() != XATTR_CAPS_SZ_2 && value.len() != XATTR_CAPS_SZ_3 {
+        return Err(Errno::EINVAL.into());
+    }
+    let capability = normalize(&value.read()?).ok_or(Errno::EINVAL)?;
+
+    let pid = notification.req.pid;
+    let ids = emulated_ids(pid, file, &fstat(file.as_raw_fd())?)?;
+    check_permission(notification, Emulated::Capability, ids.0)?;
+    check_flags(file, Emulated::Capability, flags)?;
+    set_xa_value_fd(file, Emulated::Capability, Some(&capability), ids)?;
+    Ok(Outcome::Return(0))
+}
+
+/// Stores `value` as an ACL of `file`, the ids of its entries are kept as the container sees them.
+/// Like the kernel, the permission bits of the mode follow the access ACL,
+/// which is only stored if the mode alone can not express it.
+fn set_acl(
+    notification: &Notification,
+    file: &OwnedFd,
+    xattr: Emulated,
+    value: &RemoteBuf,
+    flags: i32,
+) -> Result<Outcome, Error> {
+    if value.len() > XATTR_SIZE_MAX {
+        return Err(Errno::E2BIG.into());
+    }
+    let acl = Acl::parse(&value.read()?)?;
+
+    let pid = notification.req.pid;
+    let stat = fstat(file.as_raw_fd())?;
+    let file_type = stat.st_mode & S_IFMT;
+    if file_type == S_IFLNK {
+        return Err(Errno::EOPNOTSUPP.into());
+    }
+    if xattr == Emulated::AclDefault && file_type != S_IFDIR {
+        return if acl.is_empty() {
+            Ok(Outcome::Return(0))
+        } else {
+            Err(Errno::EACCES.into())
+        };
+    }
+    let ids = emulated_ids(pid, file, &stat)?;
+    check_permission(notification, xattr, ids.0)?;
+    check_flags(file, xattr, flags)?;
+
+    let access = xattr == Emulated::AclAccess;
+    if access && !acl.is_empty() {
+        let mode = stat.st_mode & 0o7000 | acl.mode();
+        if mode != stat.st_mode & 0o7777 {
+            stat::fchmodat(
+                None,
+                &fd_path(file),
+                Mode::from_bits_truncate(mode),
+                FchmodatFlags::FollowSymlink,
+            )?;
+        }
+    }
+    let stored = (!(acl.is_empty() || access && acl.is_minimal())).then(|| acl.to_bytes());
+    set_xa_value_fd(file, xattr, stored.as_deref(), ids)?;
+    Ok(Outcome::Return(0))
+}
+
+/// Writes the emulated xAttribute `xattr` of `file` to `value` and returns its size,
+/// an empty `value` only asks for the size
+fn get_value(
+    notification: &Notification,
+    file: &OwnedFd,
+    xattr: Emulated,
+    value: &RemoteBuf,
+) -> Result<Outcome, Error> {
+    let Some(mut stored) = find_xa_value_fd(file, xattr)? else {
+        if xattr == Emulated::Capability {
+            return Ok(Outcome::Continue);
+        }
+        return match kernel_acl(notification.req.pid, file, xattr)? {
+            Some(acl) => write_value(&acl, value),
+            None => Ok(Outcome::Continue),
+        };
+    };
+    // The entries of the access ACL represented by the mode follow later changes of it
+    if xattr == Emulated::AclAccess {
+        let mut acl = Acl::parse(&stored)?;
+        acl.set_mode(fstat(file.as_raw_fd())?.st_mode);
+        stored = acl.to_bytes();
+    }
+    write_value(&stored, value)
+}
+
+/// The ACL `xattr` the kernel stores for `file`, with the ids of its named entries translated into the user namespace of process `pid`.
+/// Returns `None` if there is none.
+fn kernel_acl(pid: u32, file: &OwnedFd, xattr: Emulated) -> Result<Option<Vec<u8>>, Error> {
+    let path = fd_path(file);
+    let mut value = match fs::getxattr(&path, xattr.name(), &mut []) {
+        Ok(size) => vec![0; size],
+        Err(rio::Errno::NODATA) => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let size = fs::getxattr(&path, xattr.name(), &mut value)?;
+    value.truncate(size);
+    let mut acl = Acl::parse(&value)?;
+
+    // The daemon reads the ids as seen from its own user namespace
+    let uid_map = IdMap::read(format!("/proc/{pid}/uid_map")).map_err(attach(Errno::ESRCH))?;
+    let gid_map = IdMap::read(format!("/proc/{pid}/gid_map")).map_err(attach(Errno::ESRCH))?;
+    for entry in &mut acl.entries {
+        match entry.tag {
+            ACL_USER => entry.id = uid_map.to_inside(entry.id).unwrap_or(OVERFLOW_ID),
+            ACL_GROUP => entry.id = gid_map.to_inside(entry.id).unwrap_or(OVERFLOW_ID),
+            _ => {}
+        }
+    }
+    Ok(Some(acl.to_bytes()))
+}
+
+/// Writes `stored` to `value` and returns its size, an empty `value` only asks for the size
+fn write_value(stored: &[u8], value: &RemoteBuf) -> Result<Outcome, Error> {
+    if !value.is_empty() {
+        value.write(stored)?;
+    }
+    Ok(Outcome::Return(
+        i64::try_from(stored.len()).map_err(|_err| Errno::ERANGE)?,
+    ))
+}
+
+/// Removes the emulated xAttribute `xattr` of `file`
+fn remove_value(
+    notification: &Notification,
+    file: &OwnedFd,
+    xattr: Emulated,
+) -> Result<Outcome, Error> {
+    if find_xa_value_fd(file, xattr)?.is_none() {
+        return Ok(Outcome::Continue);
+    }
+    let ids = emulated_ids(notification.req.pid, file, &fstat(file.as_raw_fd())?)?;
+    check_permission(notification, xattr, ids.0)?;
+    set_xa_value_fd(file, xattr, None, ids)?;
+    Ok(Outcome::Return(0))
+}
+
+/// Fails with `EPERM` if `permission_checks` are enabled and the caller may not change `xattr` of a file owned by `owner`:
+/// capabilities require `CAP_SETFCAP`, ACLs the ownership of the file or `CAP_FOWNER`
+fn check_permission(
+    notification: &Notification,
+    xattr: Emulated,
+    owner: uid_t,
+) -> Result<(), Error> {
+    if !notification.permission_checks {
+        return Ok(());
+    }
+    let creds = Credentials::read(notification.req.pid, true)?;
+    let permitted = if xattr == Emulated::Capability {
+        creds.has_capability(CAP_SETFCAP)
+    } else {
+        creds.is_owner(owner)
+    };
+    if permitted {
+        Ok(())
+    } else {
+        Err(Errno::EPERM.into())
+    }
+}
+
+/// Checks `XATTR_CREATE` and `XATTR_REPLACE` against whether `file` has the emulated xAttribute `xattr`
+fn check_flags(file: &OwnedFd, xattr: Emulated, flags: i32) -> Result<(), Error> {
+    let exists = find_xa_value_fd(file, xattr)?.is_some();
+    if flags & XATTR_CREATE != 0_i32 && exists {
+        return Err(Errno::EEXIST.into());
+    }
+    if flags & XATTR_REPLACE != 0_i32 && !exists {
+        return Err(Errno::ENODATA.into());
+    }
+    Ok(())
+}
+
+/// Validates a `struct vfs_cap_data` and turns a revision 3 one for root into revision 2,
+/// `None` if it is not a valid revision 2 or 3 one
+#[allow(clippy::little_endian_bytes)] // The fields are little endian on every architecture
+fn normalize(value: &[u8]) -> Option<Vec<u8>> {
+    let magic = u32::from_le_bytes(value.get(..4)?.try_into().ok()?);
+    let revision = magic & VFS_CAP_REVISION_MASK;
+    match value.len() {
+        XATTR_CAPS_SZ_2 if revision == VFS_CAP_REVISION_2 => Some(value.to_vec()),
+        XATTR_CAPS_SZ_3 if revision == VFS_CAP_REVISION_3 => {
+            let rootid = u32::from_le_bytes(value.get(XATTR_CAPS_SZ_2..)?.try_into().ok()?);
+            if rootid != 0 {
+                return Some(value.to_vec());
+            }
+            let magic = magic & !VFS_CAP_REVISION_MASK | VFS_CAP_REVISION_2;
+            let mut normalized = magic.to_le_bytes().to_vec();
+            normalized.extend_from_slice(value.get(4..XATTR_CAPS_SZ_2)?);
+            Some(normalized)
+        }
+        _ => None,
+    }
+}
+
+/// The file capabilities of a stored revision 2 `struct vfs_cap_data`,
+/// `None` for a revision 3 one of another user namespace, which the kernel ignores as well
+#[allow(clippy::little_endian_bytes)] // The fields are little endian on every architecture
+pub(super) fn file_capabilities(value: &[u8]) -> Option<FileCapabilities> {
+    let word = |offset: usize| {
+        let bytes = value.get(offset..offset.checked_add(4)?)?;
+        Some(u32::from_le_bytes(bytes.try_into().ok()?))
+    };
+    let magic = word(0)?;
+    if magic & VFS_CAP_REVISION_MASK != VFS_CAP_REVISION_2 || value.len() != XATTR_CAPS_SZ_2 {
+        return None;
+    }
+    // The low and high halves of the sets follow each other
+    let set = |offset: usize| {
+        Some(u64::from(word(offset)?) | (u64::from(word(offset.checked_add(8)?)?) << 32_u32))
+    };
+    Some(FileCapabilities {
+        permitted: set(4)?,
+        inheritable: set(8)?,
+        effective: magic & VFS_CAP_FLAGS_EFFECTIVE != 0,
+    })
+}
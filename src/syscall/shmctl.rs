@@ -0,0 +1,338 @@
+use nix::errno::Errno;
+use nix::libc::{
+    c_int, ipc_perm, key_t, msqid_ds, pid_t, semid_ds, shmid_ds, IPC_CREAT, IPC_EXCL, IPC_PRIVATE,
+    IPC_RMID, IPC_SET, IPC_STAT,
+};
+use nix::sched::CloneFlags;
+
+use super::{Notification, Outcome};
+use crate::creds::{CAP_IPC_OWNER, CAP_SYS_ADMIN};
+use crate::error::attach;
+use crate::identity::Identity;
+use crate::ipc::{namespace, Kind, Namespace, Owner};
+use crate::mem::{zeroed, Plain, RemoteStruct};
+use crate::nsenter::in_namespace;
+use crate::pidns::status;
+use crate::Error;
+
+/// The `struct *id_ds` of `shmctl(2)`, `semctl(2)` and `msgctl(2)`
+trait IpcDs: Plain {
+    /// The permissions of the object
+    fn perm(&mut self) -> &mut ipc_perm;
+
+    /// Sets the pid of the creator, which only shared memory reports
+    fn set_cpid(&mut self, _cpid: pid_t) {}
+}
+
+impl IpcDs for shmid_ds {
+    fn perm(&mut self) -> &mut ipc_perm {
+        &mut self.shm_perm
+    }
+
+    fn set_cpid(&mut self, cpid: pid_t) {
+        self.shm_cpid = cpid;
+    }
+}
+
+impl IpcDs for semid_ds {
+    fn perm(&mut self) -> &mut ipc_perm {
+        &mut self.sem_perm
+    }
+}
+
+impl IpcDs for msqid_ds {
+    fn perm(&mut self) -> &mut ipc_perm {
+        &mut self.msg_perm
+    }
+}
+
+/// Creates shared memory owned by the emulated effective ids of the caller, existing segments are left to the kernel
+#[subuidless::syscall]
+fn shmget(
+    notification: &Notification,
+    key: key_t,
+    size: usize,
+    shmflg: c_int,
+) -> Result<Outcome, Error> {
+    let size = u64::try_from(size).map_err(attach(Errno::EINVAL))?;
+    create(notification, Kind::SharedMemory, key, size, shmflg)
+}
+
+/// Creates a semaphore set owned by the emulated effective ids of the caller, existing sets are left to the kernel
+#[subuidless::syscall]
+fn semget(
+    notification: &Notification,
+    key: key_t,
+    nsems: u32,
+    semflg: c_int,
+) -> Result<Outcome, Error> {
+    create(
+        notification,
+        Kind::Semaphores,
+        key,
+        u64::from(nsems),
+        semflg,
+    )
+}
+
+/// Creates a message queue owned by the emulated effective ids of the caller, existing queues are left to the kernel
+#[subuidless::syscall]
+fn msgget(notification: &Notification, key: key_t, msgflg: c_int) -> Result<Outcome, Error> {
+    create(notification, Kind::MessageQueue, key, 0, msgflg)
+}
+
+/// Reports and changes the emulated owner of shared memory created in the container
+#[subuidless::syscall]
+fn shmctl(
+    notification: &Notification,
+    shmid: c_int,
+    cmd: c_int,
+    buf: RemoteStruct<shmid_ds>,
+) -> Result<Outcome, Error> {
+    control(notification, Kind::SharedMemory, shmid, cmd, buf)
+}
+
+/// Reports and changes the emulated owner of semaphore sets created in the container
+#[subuidless::syscall]
+fn semctl(
+    notification: &Notification,
+    semid: c_int,
+    _semnum: c_int,
+    cmd: c_int,
+    arg: RemoteStruct<semid_ds>,
+) -> Result<Outcome, Error> {
+    control(notification, Kind::Semaphores, semid, cmd, arg)
+}
+
+/// Reports and changes the emulated owner of message queues created in the container
+#[subuidless::syscall]
+fn msgctl(
+    notification: &Notification,
+    msqid: c_int,
+    cmd: c_int,
+    buf: RemoteStruct<msqid_ds>,
+) -> Result<Outcome, Error> {
+    control(notification, Kind::MessageQueue, msqid, cmd, buf)
+}
+
+/// Creates a new object of `kind` in the IPC namespace of the caller, owned and created by its emulated effective ids.
+/// Looking up an existing object is left to the kernel, which checks the permissions of the caller.
+fn create(
+    notification: &Notification,
+    kind: Kind,
+    key: key_t,
+    arg: u64,
+    flags: c_int,
+) -> Result<Outcome, Error> {
+    if !notification.is_native() || (key != IPC_PRIVATE && flags & IPC_CREAT == 0_i32) {
+        return Ok(Outcome::Continue);
+    }
+    let pid = notification.req.pid;
+    let namespace = namespace(pid)?;
+    let identity = Identity::get(pid)?;
+
+    // Without access to the namespace the kernel creates the object with the real ids
+    let Ok((result, _)) = in_namespace(pid, CloneFlags::CLONE_NEWIPC, 0_u8, |_| {
+        kind.get(key, arg, flags | IPC_EXCL)
+    }) else {
+        return Ok(Outcome::Continue);
+    };
+    let id = match result {
+        Ok(id) => c_int::try_from(id).map_err(attach(Errno::EINVAL))?,
+        Err(Errno::EEXIST) if flags & IPC_EXCL == 0_i32 => return Ok(Outcome::Continue),
+        Err(errno) => return Err(errno.into()),
+    };
+    let owner = Owner {
+        uid: identity.uid.effective,
+        gid: identity.gid.effective,
+        cuid: identity.uid.effective,
+        cgid: identity.gid.effective,
+        cpid: status(pid)?
+            .nstgid
+            .and_then(|nstgid| nstgid.last().copied())
+            .unwrap_or_default(),
+    };
+    // A new object may reuse the id of a removed one
+    Owner::save(namespace, kind, id, Some(owner));
+    Ok(Outcome::Return(i64::from(id)))
+}
+
+/// Reports the emulated owner for the stat commands and changes it for `IPC_SET`,
+/// other commands and objects without an emulated owner are left to the kernel
+fn control<T: IpcDs>(
+    notification: &Notification,
+    kind: Kind,
+    id: c_int,
+    cmd: c_int,
+    buf: RemoteStruct<T>,
+) -> Result<Outcome, Error> {
+    if !notification.is_native() {
+        return Ok(Outcome::Continue);
+    }
+    let namespace = namespace(notification.req.pid)?;
+    match cmd {
+        IPC_STAT if Owner::get(namespace, kind, id).is_some() => {
+            stat(notification, kind, namespace, id, cmd, buf)
+        }
+        // The index of the stat commands only turns into an id once the kernel stats the object
+        _ if (cmd == kind.stat_index() || cmd == kind.stat_any())
+            && Owner::any(namespace, kind) =>
+        {
+            stat(notification, kind, namespace, id, cmd, buf)
+        }
+        IPC_SET => match Owner::get(namespace, kind, id) {
+            Some(owner) => set(notification, kind, namespace, id, owner, &buf),
+            None => Ok(Outcome::Continue),
+        },
+        IPC_RMID => match Owner::get(namespace, kind, id) {
+            Some(owner) => remove::<T>(notification, kind, namespace, id, &owner),
+            None => Ok(Outcome::Continue),
+        },
+        _ => Ok(Outcome::Continue),
+    }
+}
+
+/// Stats the object in the IPC namespace of the caller and replaces the real ids with the emulated ones
+fn stat<T: IpcDs>(
+    notification: &Notification,
+    kind: Kind,
+    namespace: Namespace,
+    id: c_int,
+    cmd: c_int,
+    buf: RemoteStruct<T>,
+) -> Result<Outcome, Error> {
+    let pid = notification.req.pid;
+    let Ok((result, mut value)) =
+        in_namespace(pid, CloneFlags::CLONE_NEWIPC, zeroed::<T>(), |value| {
+            kind.control(id, cmd, value)
+        })
+    else {
+        return Ok(Outcome::Continue);
+    };
+    let result = match result {
+        Ok(result) => result,
+        Err(errno) => {
+            if cmd == IPC_STAT && matches!(errno, Errno::EINVAL | Errno::EIDRM) {
+                Owner::save(namespace, kind, id, None);
+            }
+            return Err(errno.into());
+        }
+    };
+
+    let id = if cmd == IPC_STAT {
+        id
+    } else {
+        c_int::try_from(result).map_err(attach(Errno::EINVAL))?
+    };
+    if let Some(owner) = Owner::get(namespace, kind, id) {
+        owner.apply(value.perm());
+        value.set_cpid(owner.cpid);
+    }
+    // The child stats the object with `CAP_IPC_OWNER`
+    if notification.permission_checks && cmd != kind.stat_any() && !may_read(pid, value.perm())? {
+        return Err(Errno::EACCES.into());
+    }
+    buf.write(value)?;
+    Ok(Outcome::Return(result))
+}
+
+/// `IPC_SET`: changes the emulated owner and the other attributes of the object, the real owner stays the same
+fn set<T: IpcDs>(
+    notification: &Notification,
+    kind: Kind,
+    namespace: Namespace,
+    id: c_int,
+    owner: Owner,
+    buf: &RemoteStruct<T>,
+) -> Result<Outcome, Error> {
+    let mut requested = buf.read()?;
+    let (uid, gid) = (requested.perm().uid, requested.perm().gid);
+    if uid == u32::MAX || gid == u32::MAX {
+        return Err(Errno::EINVAL.into());
+    }
+    check_owner(notification, &owner)?;
+
+    let Ok((result, _)) = in_namespace(
+        notification.req.pid,
+        CloneFlags::CLONE_NEWIPC,
+        requested,
+        |requested| {
+            let mut current = zeroed::<T>();
+            if kind.control(id, IPC_STAT, &mut current) == -1 {
+                return -1;
+            }
+            requested.perm().uid = current.perm().uid;
+            requested.perm().gid = current.perm().gid;
+            kind.control(id, IPC_SET, requested)
+        },
+    ) else {
+        return Ok(Outcome::Continue);
+    };
+    result?;
+    Owner::save(namespace, kind, id, Some(Owner { uid, gid, ..owner }));
+    Ok(Outcome::Return(0))
+}
+
+/// `IPC_RMID`: removes the object and forgets its emulated owner, so that it does not apply to a new object reusing the id
+fn remove<T: IpcDs>(
+    notification: &Notification,
+    kind: Kind,
+    namespace: Namespace,
+    id: c_int,
+    owner: &Owner,
+) -> Result<Outcome, Error> {
+    check_owner(notification, owner)?;
+
+    let Ok((result, _)) = in_namespace(
+        notification.req.pid,
+        CloneFlags::CLONE_NEWIPC,
+        zeroed::<T>(),
+        |value| kind.control(id, IPC_RMID, value),
+    ) else {
+        return Ok(Outcome::Continue);
+    };
+    match result {
+        Ok(_) => {
+            Owner::save(namespace, kind, id, None);
+            Ok(Outcome::Return(0))
+        }
+        Err(errno) => {
+            // The object is already gone
+            if matches!(errno, Errno::EINVAL | Errno::EIDRM) {
+                Owner::save(namespace, kind, id, None);
+            }
+            Err(errno.into())
+        }
+    }
+}
+
+/// The privilege rule of `IPC_SET` and `IPC_RMID`: only the owner, the creator or a process with `CAP_SYS_ADMIN` may change an object
+fn check_owner(notification: &Notification, owner: &Owner) -> Result<(), Error> {
+    if !notification.permission_checks {
+        return Ok(());
+    }
+    let identity = Identity::get(notification.req.pid)?;
+    let euid = identity.uid.effective;
+    if euid == owner.uid || euid == owner.cuid || identity.has_capability(CAP_SYS_ADMIN) {
+        Ok(())
+    } else {
+        Err(Errno::EPERM.into())
+    }
+}
+
+/// The read permission check of `ipcperms`: the owner and creator are checked with the bits of the owner,
+/// members of the owning or creating group with the ones of the group, and `CAP_IPC_OWNER` bypasses it
+fn may_read(pid: u32, perm: &ipc_perm) -> Result<bool, Error> {
+    let identity = Identity::get(pid)?;
+    let (euid, egid) = (identity.uid.effective, identity.gid.effective);
+    let in_group = |gid| egid == gid || identity.groups.contains(&gid);
+    let mode = perm.mode;
+    let granted = if euid == perm.uid || euid == perm.cuid {
+        mode >> 6_u32
+    } else if in_group(perm.gid) || in_group(perm.cgid) {
+        mode >> 3_u32
+    } else {
+        mode
+    };
+    Ok(granted & 0o4 != 0 || identity.has_capability(CAP_IPC_OWNER))
+}
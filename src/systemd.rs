@@ -0,0 +1,103 @@
+//! Integration with the systemd service manager
+//!
+//! Implements the receiving side of socket activation (`sd_listen_fds(3)`) and the readiness protocol (`sd_notify(3)`)
+//! without linking against `libsystemd`.
+use std::env::{remove_var, var};
+use std::os::fd::{BorrowedFd, FromRawFd, RawFd};
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram, UnixListener};
+use std::process;
+
+use anyhow::{bail, Context, Result};
+use rustix::fs::{fstat, FileType};
+use rustix::io::{fcntl_setfd, FdFlags};
+
+/// First file descriptor passed by the service manager
+const LISTEN_FDS_START: RawFd = 3;
+/// Name of the socket in `FileDescriptorName=` that is preferred if multiple sockets are passed
+const LISTEN_FDNAME: &str = "subuidless";
+
+/// Takes the listening socket passed by systemd socket activation.
+/// Returns `None` if the process was not socket activated.
+/// If several sockets are passed, the one named `subuidless` is used, otherwise the first one.
+///
+/// The `LISTEN_*` variables are removed from the environment so they are not inherited by child processes.
+pub fn listen_fd() -> Result<Option<UnixListener>> {
+    let Ok(listen_pid) = var("LISTEN_PID") else {
+        return Ok(None);
+    };
+    let listen_fds = var("LISTEN_FDS").unwrap_or_default();
+    let listen_fdnames = var("LISTEN_FDNAMES").unwrap_or_default();
+    remove_var("LISTEN_PID");
+    remove_var("LISTEN_FDS");
+    remove_var("LISTEN_FDNAMES");
+
+    if listen_pid.parse::<u32>().context("Invalid LISTEN_PID")? != process::id() {
+        return Ok(None);
+    }
+    let count: RawFd = listen_fds.parse().context("Invalid LISTEN_FDS")?;
+    if count < 1_i32 {
+        return Ok(None);
+    }
+
+    let index = listen_fdnames
+        .split(':')
+        .position(|name| name == LISTEN_FDNAME)
+        .and_then(|index| RawFd::try_from(index).ok())
+        .filter(|&index| index < count)
+        .unwrap_or_default();
+
+    for offset in 0_i32..count {
+        let fd = LISTEN_FDS_START.saturating_add(offset);
+        #[allow(unsafe_code)]
+        // SAFETY:
+        // The service manager guarantees that `LISTEN_FDS` file descriptors starting at `LISTEN_FDS_START` are open.
+        let borrowed = unsafe { BorrowedFd::borrow_raw(fd) };
+        fcntl_setfd(borrowed, FdFlags::CLOEXEC)
+            .with_context(|| format!("Could not set FD_CLOEXEC on inherited socket {fd}"))?;
+    }
+
+    let fd = LISTEN_FDS_START.saturating_add(index);
+    #[allow(unsafe_code)]
+    // SAFETY:
+    // See above, the file descriptor is open and is not used anywhere else in this process.
+    let borrowed = unsafe { BorrowedFd::borrow_raw(fd) };
+    if FileType::from_raw_mode(fstat(borrowed)?.st_mode) != FileType::Socket {
+        bail!("Inherited file descriptor {fd} is not a socket");
+    }
+
+    #[allow(unsafe_code)]
+    // SAFETY:
+    // Ownership of the socket is transferred to the `UnixListener`, `listen_fd` only takes it once
+    // because the environment is cleared above.
+    Ok(Some(unsafe { UnixListener::from_raw_fd(fd) }))
+}
+
+/// Sends `state` to the service manager, see `sd_notify(3)` for the known assignments.
+/// Does nothing if `$NOTIFY_SOCKET` is not set.
+///
+/// # Examples
+/// ```
+/// use subuidless::systemd::notify;
+///
+/// fn main() -> anyhow::Result<()> {
+///     notify("READY=1\nSTATUS=Listening")?;
+///     Ok(())
+/// }
+/// ```
+pub fn notify(state: &str) -> Result<()> {
+    let Ok(notify_socket) = var("NOTIFY_SOCKET") else {
+        return Ok(());
+    };
+
+    let address = if let Some(name) = notify_socket.strip_prefix('@') {
+        SocketAddr::from_abstract_name(name)?
+    } else {
+        SocketAddr::from_pathname(&notify_socket)?
+    };
+
+    UnixDatagram::unbound()?
+        .send_to_addr(state.as_bytes(), &address)
+        .context("Could not notify the service manager")?;
+    Ok(())
+}
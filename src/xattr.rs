@@ -1,7 +1,12 @@
 //! Modify the XA User xAttribute
 //! The main purpose of this attribute is to allow for an interoperable and standardised way of emulating persistent syscalls in a rootless container (syscalls such as chown(2) which would ordinarily fail).
 //! <https://github.com/rootless-containers/proto>
-use std::mem::size_of;
+use std::ffi::OsStr;
+use std::mem::take;
+use std::os::fd::AsFd;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
 
 use nix::errno::Errno;
 use nix::libc::{gid_t, uid_t};
@@ -11,11 +16,14 @@ use rustix::{fs, path};
 
 use crate::error::attach;
 use crate::proto::rootlesscontainers::Resource;
+use crate::resolve::fd_path;
 
 const XA_USER_ROOTLESSCONTAINERS: &str = "user.rootlesscontainers";
 
 /// Set the `XA_USER_ROOTLESSCONTAINERS` xAttribute of a file.
-/// If the uid & gid are both equal to 0 the xAttribute is removed
+/// If the uid & gid are both equal to 0 the xAttribute is removed.
+/// Like `chown(2)`, the set-user-ID bit of files other than directories is cleared,
+/// and their set-group-ID bit if members of the group may execute them.
 ///
 /// # Examples
 ///
@@ -42,24 +50,57 @@ pub fn set_xa_user<P: path::Arg + Clone>(
         fs::lremovexattr
     };
 
+    // chown(2) clears the capabilities of a file, its ACLs are kept
+    let (acl_access, acl_default) = read_resource(path.clone(), follow)?
+        .map(|resource| (resource.acl_access, resource.acl_default))
+        .unwrap_or_default();
     if uid == 0 && gid == 0 {
         removexattr(path.clone(), XA_USER_ROOTLESSCONTAINERS)?;
     }
     let resource = Resource {
         uid,
         gid,
+        acl_access,
+        acl_default,
         ..Default::default()
     };
 
     let setxattr = if follow { fs::setxattr } else { fs::lsetxattr };
 
     setxattr(
-        path,
+        path.clone(),
         XA_USER_ROOTLESSCONTAINERS,
         &resource.write_to_bytes().map_err(attach(Errno::ENOTSUP))?,
         fs::XattrFlags::empty(),
     )?;
 
+    clear_setid(path, follow)
+}
+
+/// Clears the set-user-ID and set-group-ID bits of a file like `chown(2)` does, see `set_xa_user`
+fn clear_setid<P: path::Arg + Clone>(path: P, follow: bool) -> Result<(), crate::Error> {
+    let stat = if follow {
+        fs::stat(path.clone())
+    } else {
+        fs::lstat(path.clone())
+    }?;
+    // The mode of a symlink can not be changed and has no set-id bits
+    if matches!(
+        fs::FileType::from_raw_mode(stat.st_mode),
+        fs::FileType::Directory | fs::FileType::Symlink
+    ) {
+        return Ok(());
+    }
+    let mode = fs::Mode::from_raw_mode(stat.st_mode);
+    let mut cleared = mode;
+    cleared.remove(fs::Mode::SUID);
+    // Without group execute permission the set-group-ID bit marks mandatory locking instead
+    if mode.contains(fs::Mode::XGRP) {
+        cleared.remove(fs::Mode::SGID);
+    }
+    if cleared != mode {
+        fs::chmod(path, cleared)?;
+    }
     Ok(())
 }
 
@@ -87,24 +128,211 @@ pub fn get_xa_user<P: path::Arg + Clone>(
     path: P,
     follow: bool,
 ) -> Result<(uid_t, gid_t), crate::Error> {
-    let mut buf = vec![0; size_of::<Resource>()];
+    Ok(find_xa_user(path, follow)?.unwrap_or((0, 0)))
+}
+
+/// Get the `XA_USER_ROOTLESSCONTAINERS` xAttribute of a file, `None` if it is not set
+fn find_xa_user<P: path::Arg + Clone>(
+    path: P,
+    follow: bool,
+) -> Result<Option<(uid_t, gid_t)>, crate::Error> {
+    Ok(read_resource(path, follow)?.map(|resource| (resource.uid, resource.gid)))
+}
 
+/// Parse the `XA_USER_ROOTLESSCONTAINERS` xAttribute of a file, `None` if it is not set
+fn read_resource<P: path::Arg + Clone>(
+    path: P,
+    follow: bool,
+) -> Result<Option<Resource>, crate::Error> {
     let getxattr = if follow { fs::getxattr } else { fs::lgetxattr };
 
-    let size = match getxattr(path, XA_USER_ROOTLESSCONTAINERS, &mut buf) {
-        Ok(size) => Ok(size),
+    // An empty buffer asks for the size of the value
+    let len = match getxattr(path.clone(), XA_USER_ROOTLESSCONTAINERS, &mut []) {
+        Ok(len) => Ok(len),
         Err(err) => {
             if err == rio::Errno::NODATA {
-                return Ok((0, 0));
+                return Ok(None);
             }
             Err(err)
         }
     }?;
-
+    let mut buf = vec![0; len];
+    let size = getxattr(path, XA_USER_ROOTLESSCONTAINERS, &mut buf)?;
     buf.truncate(size);
-    buf.shrink_to_fit();
 
     let resource = Resource::parse_from_bytes(&buf).map_err(attach(Errno::ENOTSUP))?;
 
-    Ok((resource.uid, resource.gid))
+    Ok(Some(resource))
+}
+
+/// Set the `XA_USER_ROOTLESSCONTAINERS` xAttribute of the file behind `fd`, which may be an `O_PATH` descriptor.
+/// See `set_xa_user` and `checked`
+pub fn set_xa_user_fd<Fd: AsFd>(fd: Fd, uid: uid_t, gid: gid_t) -> Result<(), crate::Error> {
+    checked(fd, |path| set_xa_user(path, true, uid, gid))
+}
+
+/// Get the `XA_USER_ROOTLESSCONTAINERS` xAttribute of the file behind `fd`, which may be an `O_PATH` descriptor.
+/// See `get_xa_user`
+///
+/// # Examples
+///
+/// ```
+/// # use anyhow::Result;
+/// use std::fs::File;
+/// use subuidless::xattr::{get_xa_user_fd, set_xa_user_fd};
+///
+/// fn main() -> Result<()> {
+///     let file = File::create("/tmp/example-fd")?;
+///     set_xa_user_fd(&file, 1000, 1000)?;
+///     assert_eq!(get_xa_user_fd(&file)?, (1000, 1000));
+///     Ok(())
+/// }
+/// ```
+pub fn get_xa_user_fd<Fd: AsFd>(fd: Fd) -> Result<(uid_t, gid_t), crate::Error> {
+    checked(fd, |path| get_xa_user(path, true))
+}
+
+/// Get the `XA_USER_ROOTLESSCONTAINERS` xAttribute of the file behind `fd`, `None` if the ownership is not emulated.
+/// See `get_xa_user_fd`
+///
+/// # Examples
+///
+/// ```
+/// # use anyhow::Result;
+/// use std::fs::File;
+/// use subuidless::xattr::find_xa_user_fd;
+///
+/// fn main() -> Result<()> {
+///     let file = File::create("/tmp/example-find")?;
+///     assert_eq!(find_xa_user_fd(&file)?, None);
+///     Ok(())
+/// }
+/// ```
+pub fn find_xa_user_fd<Fd: AsFd>(fd: Fd) -> Result<Option<(uid_t, gid_t)>, crate::Error> {
+    checked(fd, |path| find_xa_user(path, true))
+}
+
+/// xAttributes whose values are stored in the `XA_USER_ROOTLESSCONTAINERS` xAttribute instead of the file itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::exhaustive_enums)] // Every emulated xAttribute needs a field in `Resource`
+pub enum Emulated {
+    /// `security.capability`, the capabilities of an executable
+    Capability,
+    /// `system.posix_acl_access`, the ACL checked when accessing the file
+    AclAccess,
+    /// `system.posix_acl_default`, the ACL inherited by new files in a directory
+    AclDefault,
+}
+
+impl Emulated {
+    /// The emulated xAttribute called `name`, `None` if it is passed to the filesystem
+    #[must_use]
+    pub fn from_name(name: &OsStr) -> Option<Self> {
+        match name.to_str()? {
+            "security.capability" => Some(Self::Capability),
+            "system.posix_acl_access" => Some(Self::AclAccess),
+            "system.posix_acl_default" => Some(Self::AclDefault),
+            _ => None,
+        }
+    }
+
+    /// The name of the xAttribute
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Capability => "security.capability",
+            Self::AclAccess => "system.posix_acl_access",
+            Self::AclDefault => "system.posix_acl_default",
+        }
+    }
+
+    /// The field of `resource` holding the value
+    fn field(self, resource: &mut Resource) -> &mut Vec<u8> {
+        match self {
+            Self::Capability => &mut resource.capability,
+            Self::AclAccess => &mut resource.acl_access,
+            Self::AclDefault => &mut resource.acl_default,
+        }
+    }
+}
+
+/// Get the value of the emulated xAttribute `xattr` of the file behind `fd`, `None` if it is not set
+///
+/// # Examples
+///
+/// ```
+/// # use anyhow::Result;
+/// use std::fs::File;
+/// use subuidless::xattr::{find_xa_value_fd, set_xa_value_fd, Emulated};
+///
+/// fn main() -> Result<()> {
+///     let file = File::create("/tmp/example-capability")?;
+///     let capability = [0, 0, 0, 2, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+///     set_xa_value_fd(&file, Emulated::Capability, Some(&capability), (0, 0))?;
+///     assert_eq!(find_xa_value_fd(&file, Emulated::Capability)?, Some(capability.to_vec()));
+///     assert_eq!(find_xa_value_fd(&file, Emulated::AclAccess)?, None);
+///     set_xa_value_fd(&file, Emulated::Capability, None, (0, 0))?;
+///     assert_eq!(find_xa_value_fd(&file, Emulated::Capability)?, None);
+///     Ok(())
+/// }
+/// ```
+pub fn find_xa_value_fd<Fd: AsFd>(
+    fd: Fd,
+    xattr: Emulated,
+) -> Result<Option<Vec<u8>>, crate::Error> {
+    Ok(checked(fd, |path| read_resource(path, true))?
+        .map(|mut resource| take(xattr.field(&mut resource)))
+        .filter(|value| !value.is_empty()))
+}
+
+/// Store `value` as the emulated xAttribute `xattr` of the file behind `fd`, `None` removes it.
+/// The ownership is kept, files without emulated ownership get `ids`.
+pub fn set_xa_value_fd<Fd: AsFd>(
+    fd: Fd,
+    xattr: Emulated,
+    value: Option<&[u8]>,
+    ids: (uid_t, gid_t),
+) -> Result<(), crate::Error> {
+    checked(fd, |path| {
+        let mut resource = read_resource(path.clone(), true)?.unwrap_or_else(|| Resource {
+            uid: ids.0,
+            gid: ids.1,
+            ..Default::default()
+        });
+        *xattr.field(&mut resource) = value.map(<[u8]>::to_vec).unwrap_or_default();
+
+        fs::setxattr(
+            path,
+            XA_USER_ROOTLESSCONTAINERS,
+            &resource.write_to_bytes().map_err(attach(Errno::ENOTSUP))?,
+            fs::XattrFlags::empty(),
+        )?;
+        Ok(())
+    })
+}
+
+/// Runs `operation` on `/proc/self/fd/<fd>` and checks that it led to the same file as `fd` before and after it,
+/// failing with `ESTALE` otherwise.
+/// xAttributes can not be accessed through `O_PATH` descriptors, only through their magic link.
+fn checked<Fd: AsFd, T, F: FnOnce(PathBuf) -> Result<T, crate::Error>>(
+    fd: Fd,
+    operation: F,
+) -> Result<T, crate::Error> {
+    let expected = fs::fstat(&fd)?;
+    let path = fd_path(&fd.as_fd());
+    let same_file = || -> Result<(), crate::Error> {
+        let actual = fs::stat(&path)?;
+        if (expected.st_dev, expected.st_ino) == (actual.st_dev, actual.st_ino) {
+            Ok(())
+        } else {
+            Err(attach(Errno::ESTALE)(anyhow!(
+                "{} does not lead to the resolved file",
+                path.display()
+            )))
+        }
+    };
+    same_file()?;
+    let result = operation(path.clone())?;
+    same_file()?;
+    Ok(result)
 }
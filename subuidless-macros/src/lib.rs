@@ -0,0 +1,390 @@
+//! Attribute macro implementing the `Syscall` trait of subuidless for a handler function
+//!
+//! Use it through the re-export `subuidless::syscall`, the generated code refers to paths of the `subuidless` crate.
+use std::mem::take;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote, ToTokens};
+use syn::meta::{self, ParseNestedMeta};
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, parse_quote, Attribute, Error, Expr, FnArg, Ident, ItemFn, LitStr, PatType,
+    Result, Type,
+};
+
+/// Number of arguments a syscall can take, see `syscall(2)`
+const MAX_ARGS: usize = 6;
+
+/// Implements a syscall handler, registering it for the syscall `name` on the architectures `archs`
+///
+/// The first parameter of the function receives the `&Notification`, every following one the next syscall argument.
+/// Arguments are converted with `TryFrom<MaybeRemote>` unless they are marked with one of:
+/// * `#[fd]`: A file descriptor of the caller, converted with `FromFd`
+/// * `#[remote]`: A `T: Plain` read from the memory of the caller
+/// * `#[out]`: A `&mut T` that starts zeroed and is written to the caller once the handler succeeded,
+///   combined with `#[remote]` it starts with the value read from the caller
+///
+/// `name` defaults to the name of the function, `archs` to all supported architectures.
+/// It takes an array of `ScmpArch` or an expression returning a `Vec<ScmpArch>`.
+/// Handlers marked with `permission_check` are only enabled by `Config::permission_checks`.
+/// The function returns a `Result` of an `Outcome` or of a value the syscall returns.
+/// `#[out]` arguments are only written if the outcome is `Outcome::Return`.
+///
+/// ```ignore
+/// /// Stats a file relative to a directory file descriptor
+/// #[subuidless::syscall(name = "newfstatat", archs = [ScmpArch::X8664])]
+/// fn newfstatat(
+///     notification: &Notification,
+///     #[fd] dirfd: Option<RawFd>,
+///     pathname: PathBuf,
+///     #[out] statbuf: &mut FileStat,
+///     flags: AtFlags,
+/// ) -> Result<Outcome, Error> {
+///     ...
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn syscall(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut options = Options::default();
+    let parser = meta::parser(|meta| options.parse(&meta));
+    parse_macro_input!(attr with parser);
+    let function = parse_macro_input!(item as ItemFn);
+
+    expand(&options, function)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// Options given to the attribute
+#[derive(Default)]
+struct Options {
+    /// Name of the syscall as known to libseccomp
+    name: Option<LitStr>,
+    /// Architectures the handler is registered for
+    archs: Option<Expr>,
+    /// Whether the handler emulates permission checks
+    permission_check: bool,
+}
+
+impl Options {
+    /// Parses `name = "..."`, `archs = ...` and `permission_check`
+    fn parse(&mut self, meta: &ParseNestedMeta<'_>) -> Result<()> {
+        if meta.path.is_ident("permission_check") {
+            self.permission_check = true;
+            Ok(())
+        } else if meta.path.is_ident("name") {
+            self.name = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("archs") {
+            self.archs = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("Expected `name`, `archs` or `permission_check`"))
+        }
+    }
+
+    /// Expression building the `Vec<ScmpArch>` of the handler
+    fn archs(&self) -> TokenStream2 {
+        match self.archs.clone() {
+            Some(Expr::Array(array)) => {
+                let elems = array.elems;
+                quote!(::std::vec![#elems])
+            }
+            Some(archs) => archs.to_token_stream(),
+            None => quote!(::subuidless::abi::supported_archs()),
+        }
+    }
+}
+
+/// How a syscall argument is passed to the handler
+enum Kind {
+    /// Converted with `TryFrom<MaybeRemote>`
+    Value,
+    /// Converted with `FromFd`
+    Fd,
+    /// Read from the memory of the caller
+    Remote,
+    /// Written to the memory of the caller after the handler succeeded, read before if `read` is set
+    Out {
+        /// Whether the initial value is read from the caller
+        read: bool,
+    },
+}
+
+impl Kind {
+    /// Determines the kind from the attributes `#[fd]`, `#[remote]` and `#[out]` and removes them
+    fn take(arg: &mut PatType) -> Result<Self> {
+        let mut fd = false;
+        let mut remote = false;
+        let mut out = false;
+        let mut attrs = Vec::new();
+        for attr in arg.attrs.drain(..) {
+            if attr.path().is_ident("fd") {
+                fd = marker(&attr, fd)?;
+            } else if attr.path().is_ident("remote") {
+                remote = marker(&attr, remote)?;
+            } else if attr.path().is_ident("out") {
+                out = marker(&attr, out)?;
+            } else {
+                attrs.push(attr);
+            }
+        }
+        arg.attrs = attrs;
+
+        match (fd, remote, out) {
+            (false, false, false) => Ok(Self::Value),
+            (true, false, false) => Ok(Self::Fd),
+            (false, true, false) => {
+                if matches!(*arg.ty, Type::Reference(_)) {
+                    return Err(Error::new(
+                        arg.ty.span(),
+                        "`#[remote]` arguments are passed by value, add `#[out]` to write them back",
+                    ));
+                }
+                Ok(Self::Remote)
+            }
+            (false, read, true) => Ok(Self::Out { read }),
+            (true, _, _) => Err(Error::new(
+                arg.span(),
+                "`#[fd]` cannot be combined with `#[remote]` or `#[out]`",
+            )),
+        }
+    }
+}
+
+/// Checks that `attr` is a plain marker that was not given before
+fn marker(attr: &Attribute, given: bool) -> Result<bool> {
+    attr.meta.require_path_only()?;
+    if given {
+        return Err(Error::new(attr.span(), "Duplicate attribute"));
+    }
+    Ok(true)
+}
+
+/// The target of a `&mut T` type
+fn referent(ty: Type) -> Result<Type> {
+    let span = ty.span();
+    if let Type::Reference(reference) = ty {
+        if reference.mutability.is_some() {
+            return Ok(*reference.elem);
+        }
+    }
+    Err(Error::new(span, "`#[out]` arguments have to be a `&mut T`"))
+}
+
+/// Name of the struct implementing `Syscall`: the name of the function in `UpperCamelCase`
+fn struct_name(function: &Ident) -> Ident {
+    let name: String = function
+        .to_string()
+        .split('_')
+        .flat_map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .into_iter()
+                .flat_map(char::to_uppercase)
+                .chain(chars)
+        })
+        .collect();
+    format_ident!("{}Syscall", name)
+}
+
+/// The code generated for a syscall argument
+struct Argument {
+    /// Converts the raw argument into the local variable passed to the handler
+    conversion: TokenStream2,
+    /// Expression passing the variable to the handler
+    call: TokenStream2,
+    /// Writes the variable back to the caller once the handler succeeded
+    write: Option<TokenStream2>,
+    /// Row of the argument table in the documentation
+    doc: String,
+}
+
+impl Argument {
+    /// Generates the code for the syscall argument `index`, passed to the parameter `arg`
+    fn new(index: usize, arg: &mut PatType) -> Result<Self> {
+        if index >= MAX_ARGS {
+            return Err(Error::new(
+                arg.span(),
+                format!("Syscalls take at most {MAX_ARGS} arguments"),
+            ));
+        }
+        let kind = Kind::take(arg)?;
+        let ty = &arg.ty;
+        let var = format_ident!("arg{}", index);
+        let remote = format_ident!("remote{}", index);
+        let source = quote!(notification.arg(#index)?);
+
+        let (conversion, call, write, passed) = match kind {
+            Kind::Value => (
+                quote!(let #var: #ty = ::std::convert::TryFrom::try_from(#source)?;),
+                quote!(#var),
+                None,
+                "value",
+            ),
+            Kind::Fd => (
+                quote!(let #var: #ty = ::subuidless::mem::FromFd::from_fd(#source)?;),
+                quote!(#var),
+                None,
+                "file descriptor",
+            ),
+            Kind::Remote => (
+                quote!(
+                    let #var: #ty = ::subuidless::mem::RemoteStruct::<#ty>::try_from(#source)?.read()?;
+                ),
+                quote!(#var),
+                None,
+                "read from the caller",
+            ),
+            Kind::Out { read } => {
+                let referent = referent(*ty.clone())?;
+                let initial = if read {
+                    quote!(#remote.read()?)
+                } else {
+                    quote!(::subuidless::mem::zeroed::<#referent>())
+                };
+                (
+                    quote!(
+                        let #remote = ::subuidless::mem::RemoteStruct::<#referent>::try_from(#source)?;
+                        let mut #var: #referent = #initial;
+                    ),
+                    quote!(&mut #var),
+                    Some(quote!(#remote.write(#var)?;)),
+                    if read {
+                        "read from and written to the caller"
+                    } else {
+                        "written to the caller"
+                    },
+                )
+            }
+        };
+        let doc = format!("| {index} | `{}` | {passed} |", arg.pat.to_token_stream());
+        Ok(Self {
+            conversion,
+            call,
+            write,
+            doc,
+        })
+    }
+}
+
+/// Generates the handler function, the struct implementing `Syscall` and its registration
+#[allow(clippy::too_many_lines)] // Most of it is the generated code
+fn expand(options: &Options, mut function: ItemFn) -> Result<TokenStream2> {
+    let span = function.sig.span();
+    let mut inputs = take(&mut function.sig.inputs).into_iter();
+    let Some(FnArg::Typed(notification)) = inputs.next() else {
+        return Err(Error::new(
+            span,
+            "The first parameter of a syscall handler has to receive the `&Notification`",
+        ));
+    };
+    function.sig.inputs.push(FnArg::Typed(notification));
+
+    let mut arguments = Vec::new();
+    for (index, input) in inputs.enumerate() {
+        let FnArg::Typed(mut arg) = input else {
+            return Err(Error::new(
+                input.span(),
+                "Syscall handlers cannot take `self`",
+            ));
+        };
+        arguments.push(Argument::new(index, &mut arg)?);
+        function.sig.inputs.push(FnArg::Typed(arg));
+    }
+    let conversions = arguments.iter().map(|argument| &argument.conversion);
+    let call = arguments.iter().map(|argument| &argument.call);
+    let writes: Vec<_> = arguments
+        .iter()
+        .filter_map(|argument| argument.write.as_ref())
+        .collect();
+    // The kernel fills the out arguments when it executes the syscall, and the caller must not see them change once answered
+    let writes = if writes.is_empty() {
+        TokenStream2::new()
+    } else {
+        quote!(if let ::subuidless::syscall::Outcome::Return(_) = outcome { #(#writes)* })
+    };
+    let mut docs: Vec<String> = arguments
+        .iter()
+        .map(|argument| argument.doc.clone())
+        .collect();
+
+    let handler = &function.sig.ident;
+    let name = options
+        .name
+        .clone()
+        .unwrap_or_else(|| LitStr::new(&handler.to_string(), handler.span()));
+    let archs = options.archs();
+    let permission_check = options.permission_check;
+    let implementation = struct_name(handler);
+    let vis = &function.vis;
+
+    let mut doc = vec![
+        String::new(),
+        "# Syscall".to_owned(),
+        format!("Handles `{}`, see `{}(2)`.", name.value(), name.value()),
+    ];
+    if !docs.is_empty() {
+        doc.push(String::new());
+        doc.push("| Argument | Parameter | Passed as |".to_owned());
+        doc.push("|---|---|---|".to_owned());
+        doc.append(&mut docs);
+    }
+    function.attrs.extend(
+        doc.iter()
+            .map(|line| -> Attribute { parse_quote!(#[doc = #line]) }),
+    );
+    // The handler takes the converted arguments by value, even if it only borrows them
+    function
+        .attrs
+        .push(parse_quote!(#[allow(clippy::needless_pass_by_value)]));
+
+    let struct_doc = format!(
+        "Implements `Syscall` for `{name}` with [`{handler}`]",
+        name = name.value()
+    );
+    Ok(quote! {
+        #function
+
+        #[doc = #struct_doc]
+        #vis struct #implementation;
+
+        impl ::subuidless::syscall::Syscall for #implementation {
+            fn execute(
+                &self,
+                notification: ::subuidless::syscall::Notification,
+            ) -> ::core::result::Result<::subuidless::syscall::Outcome, ::subuidless::Error> {
+                #(#conversions)*
+                let outcome: ::subuidless::syscall::Outcome =
+                    ::core::convert::From::from(#handler(&notification, #(#call),*)?);
+                #writes
+                ::core::result::Result::Ok(outcome)
+            }
+
+            fn get_syscall(
+                &self,
+                arch: ::subuidless::__private::libseccomp::ScmpArch,
+            ) -> ::subuidless::__private::anyhow::Result<::subuidless::__private::libseccomp::ScmpSyscall> {
+                ::core::result::Result::Ok(::subuidless::__private::libseccomp::ScmpSyscall::from_name_by_arch(#name, arch)?)
+            }
+
+            fn get_archs(&self) -> ::std::vec::Vec<::subuidless::__private::libseccomp::ScmpArch> {
+                #archs
+            }
+
+            fn get_name(&self) -> ::std::string::String {
+                ::std::borrow::ToOwned::to_owned(#name)
+            }
+
+            fn is_permission_check(&self) -> bool {
+                #permission_check
+            }
+        }
+
+        ::subuidless::__private::inventory::submit! {
+            &#implementation as &dyn ::subuidless::syscall::Syscall
+        }
+    })
+}
@@ -0,0 +1,49 @@
+use std::fs::File;
+
+use nix::libc::{gid_t, uid_t};
+use proptest::prelude::*;
+use rustix::fs::{getxattr, setxattr, XattrFlags};
+use subuidless::acl::{
+    Acl, Entry, ACL_GROUP, ACL_GROUP_OBJ, ACL_MASK, ACL_OTHER, ACL_USER, ACL_USER_OBJ,
+};
+use subuidless_test::syscall;
+
+use crate::fchownat::id_strategy;
+
+syscall!(
+    AclRoundTrip {
+        #[proptest(strategy = "id_strategy()")]
+        user: uid_t,
+        #[proptest(strategy = "id_strategy()")]
+        group: gid_t,
+        #[proptest(strategy = "0..8_u16")]
+        perm: u16
+    },
+    // Act
+    self {
+        let path = "/tmp/acl";
+        File::create(path)?;
+        let entry = |tag, perm, id| Entry { tag, perm, id };
+        // What `setfacl -m u:<user>:<perm>,g:<group>:<perm>` stores
+        let acl = Acl {
+            entries: vec![
+                entry(ACL_USER_OBJ, 6, u32::MAX),
+                entry(ACL_USER, self.perm, self.user),
+                entry(ACL_GROUP_OBJ, 4, u32::MAX),
+                entry(ACL_GROUP, self.perm, self.group),
+                entry(ACL_MASK, 7, u32::MAX),
+                entry(ACL_OTHER, 4, u32::MAX),
+            ],
+        };
+        setxattr(path, "system.posix_acl_access", &acl.to_bytes(), XattrFlags::empty())?;
+        // What `getfacl` reads
+        let mut value = vec![0; getxattr(path, "system.posix_acl_access", &mut [])?];
+        let len = getxattr(path, "system.posix_acl_access", &mut value)?;
+        value.truncate(len);
+        (acl.to_bytes(), value)
+    },
+    // Assert
+    test_acl_round_trip(acl_round_trip, (left,right): (Vec<u8>, Vec<u8>)) {
+        prop_assert_eq!(left, right);
+        Ok::<(),TestCaseError>(())
+});
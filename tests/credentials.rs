@@ -0,0 +1,70 @@
+use std::io::{IoSlice, IoSliceMut};
+
+use nix::libc::{gid_t, pid_t, uid_t};
+use nix::unistd::{setresuid, Uid};
+use proptest::prelude::*;
+use rustix::net::sockopt::set_socket_passcred;
+use rustix::net::{
+    recvmsg, sendmsg, socketpair, AddressFamily, RecvAncillaryBuffer, RecvAncillaryMessage,
+    RecvFlags, SendAncillaryBuffer, SendAncillaryMessage, SendFlags, SocketFlags, SocketType,
+    UCred,
+};
+use rustix::process::{getgid, getpid, getuid, Pid};
+use subuidless_test::syscall;
+
+use crate::fchownat::id_strategy;
+
+/// The pid, user and group id of `cred`
+fn ids(cred: &UCred) -> (pid_t, uid_t, gid_t) {
+    (
+        Pid::as_raw(Some(cred.pid)),
+        cred.uid.as_raw(),
+        cred.gid.as_raw(),
+    )
+}
+
+syscall!(
+    Credentials {
+        #[proptest(strategy = "id_strategy()")]
+        uid: uid_t
+    },
+    // Act
+    self {
+        let root = Uid::from_raw(0);
+        let (sender, receiver) =
+            socketpair(AddressFamily::UNIX, SocketType::DGRAM, SocketFlags::CLOEXEC, None)?;
+        set_socket_passcred(&receiver, true)?;
+        // The saved uid stays root, so the process can switch back
+        setresuid(Uid::from_raw(self.uid), Uid::from_raw(self.uid), root)?;
+
+        let sent = UCred {
+            pid: getpid(),
+            uid: getuid(),
+            gid: getgid(),
+        };
+        let mut send_space = [0_u8; rustix::cmsg_space!(ScmCredentials(1))];
+        let mut control = SendAncillaryBuffer::new(&mut send_space);
+        control.push(SendAncillaryMessage::ScmCredentials(sent));
+        sendmsg(&sender, &[IoSlice::new(b"subuidless")], &mut control, SendFlags::empty())?;
+
+        let mut data = [0_u8; 16];
+        let mut receive_space = [0_u8; rustix::cmsg_space!(ScmCredentials(1))];
+        let mut control = RecvAncillaryBuffer::new(&mut receive_space);
+        recvmsg(&receiver, &mut [IoSliceMut::new(&mut data)], &mut control, RecvFlags::empty())?;
+        let received: Vec<_> = control
+            .drain()
+            .filter_map(|message| match message {
+                RecvAncillaryMessage::ScmCredentials(cred) => Some(ids(&cred)),
+                _ => None,
+            })
+            .collect();
+        setresuid(root, root, root)?;
+        (self.uid, ids(&sent), received)
+    },
+    // Assert
+    test_credentials(credentials, (uid, sent, received): (uid_t, (pid_t, uid_t, gid_t), Vec<(pid_t, uid_t, gid_t)>)) {
+        prop_assert_eq!(sent.1, uid);
+        // The receiver sees the emulated ids the sender passed
+        prop_assert_eq!(received, vec![sent]);
+        Ok::<(),TestCaseError>(())
+});
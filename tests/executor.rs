@@ -1,8 +1,26 @@
 //!
 #[cfg(test)]
+mod acl;
+#[cfg(test)]
+mod credentials;
+#[cfg(test)]
+mod execve;
+#[cfg(test)]
+mod faccessat;
+#[cfg(test)]
+mod fchmodat;
+#[cfg(test)]
 mod fchownat;
 #[cfg(test)]
+mod keep_ids;
+#[cfg(test)]
 mod newfstatat;
+#[cfg(test)]
+mod non_utf8;
+#[cfg(test)]
+mod setuid;
+#[cfg(test)]
+mod shmctl;
 
 #[cfg(feature = "executor")]
 subuidless_test::create_docker!(
@@ -0,0 +1,38 @@
+use std::fs::{copy, metadata, set_permissions, Permissions};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+use nix::libc::{uid_t, S_ISUID};
+use nix::unistd::{chown, Uid};
+use proptest::prelude::*;
+use subuidless_test::syscall;
+
+use crate::fchownat::id_strategy;
+
+syscall!(
+    Execve {
+        #[proptest(strategy = "id_strategy()")]
+        uid: uid_t
+    },
+    // Act
+    self {
+        let path = "/tmp/setuid-id";
+        copy("/usr/bin/id", path)?;
+        chown(path, Some(Uid::from_raw(self.uid)), None)?;
+        set_permissions(path, Permissions::from_mode(0o4755))?;
+        // The name selects the applet if `id` is a multi-call binary
+        let output = Command::new(path).arg0("id").arg("-u").output()?;
+        let euid = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+        // The owner changes, so the set-user-ID bit is cleared
+        chown(path, Some(Uid::from_raw(0)), None)?;
+        let mode = metadata(path)?.permissions().mode();
+        (self.uid, euid, mode)
+    },
+    // Assert
+    test_execve(execve, (uid, euid, mode): (uid_t, String, u32)) {
+        // The set-user-ID executable runs with its emulated owner as effective uid
+        prop_assert_eq!(euid, uid.to_string());
+        prop_assert_eq!(mode & S_ISUID, 0);
+        Ok::<(),TestCaseError>(())
+});
@@ -0,0 +1,40 @@
+use std::fs::{set_permissions, File, Permissions};
+use std::os::unix::fs::PermissionsExt;
+
+use nix::errno::Errno;
+use nix::libc::uid_t;
+use nix::unistd::{access, chown, setresuid, AccessFlags, Uid};
+use proptest::prelude::*;
+use subuidless_test::syscall;
+
+syscall!(
+    Access {
+        #[proptest(strategy = "1..1000_u32")]
+        owner: uid_t
+    },
+    // Act
+    self {
+        let path = "/tmp/access";
+        let root = Uid::from_raw(0);
+        // Root would be granted access by its capabilities, so the other uid is never root
+        let other = self.owner.saturating_add(1);
+        File::create(path)?;
+        chown(path, Some(Uid::from_raw(self.owner)), None)?;
+        set_permissions(path, Permissions::from_mode(0o600))?;
+
+        // The saved uid stays root, so the process can switch back
+        setresuid(Uid::from_raw(self.owner), Uid::from_raw(self.owner), root)?;
+        let owner_access = access(path, AccessFlags::R_OK);
+        setresuid(root, root, root)?;
+        setresuid(Uid::from_raw(other), Uid::from_raw(other), root)?;
+        let other_access = access(path, AccessFlags::R_OK);
+        setresuid(root, root, root)?;
+        (owner_access, other_access)
+    },
+    // Assert
+    test_access(access, (owner_access, other_access): (Result<(), Errno>, Result<(), Errno>)) {
+        // Requires `permission_checks`, see `tests/config.json`
+        prop_assert_eq!(owner_access, Ok(()));
+        prop_assert_eq!(other_access, Err(Errno::EACCES));
+        Ok::<(),TestCaseError>(())
+});
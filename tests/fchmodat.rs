@@ -0,0 +1,52 @@
+use std::fs::{metadata, set_permissions, File, Permissions};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+use nix::errno::Errno;
+use nix::libc::uid_t;
+use nix::unistd::{chown, setgroups, setresuid, Gid, Uid};
+use proptest::prelude::*;
+use subuidless_test::syscall;
+
+syscall!(
+    Privileges {
+        #[proptest(strategy = "1..=1000_u32")]
+        uid: uid_t
+    },
+    // Act
+    self {
+        let (root_file, own_file) = ("/tmp/root-owned", "/tmp/user-owned");
+        let root = Uid::from_raw(0);
+        let (uid, group) = (Uid::from_raw(self.uid), Gid::from_raw(self.uid));
+        File::create(root_file)?;
+        File::create(own_file)?;
+        chown(root_file, Some(root), Some(Gid::from_raw(0)))?;
+        set_permissions(root_file, Permissions::from_mode(0o644))?;
+        chown(own_file, Some(uid), Some(Gid::from_raw(0)))?;
+        setgroups(&[group])?;
+
+        // The saved uid stays root, so the process can switch back
+        setresuid(uid, uid, root)?;
+        let chown_root = chown(root_file, Some(uid), None).err();
+        let chmod_root = set_permissions(root_file, Permissions::from_mode(0o777))
+            .err()
+            .and_then(|err| err.raw_os_error())
+            .map(Errno::from_raw);
+        let chgrp_own = chown(own_file, None, Some(group)).err();
+        setresuid(root, root, root)?;
+        setgroups(&[])?;
+
+        let root_meta = metadata(root_file)?;
+        let own_meta = metadata(own_file)?;
+        (self.uid, chown_root, chmod_root, chgrp_own, root_meta.uid(), root_meta.mode() & 0o777, own_meta.gid())
+    },
+    // Assert
+    test_privileges(privileges, (uid, chown_root, chmod_root, chgrp_own, root_uid, root_mode, own_gid): (uid_t, Option<Errno>, Option<Errno>, Option<Errno>, u32, u32, u32)) {
+        // Requires `permission_checks`, see `tests/config.json`
+        prop_assert_eq!(chown_root, Some(Errno::EPERM));
+        prop_assert_eq!(chmod_root, Some(Errno::EPERM));
+        prop_assert_eq!((root_uid, root_mode), (0, 0o644));
+        // The owner may change the group to one of its groups
+        prop_assert_eq!(chgrp_own, None);
+        prop_assert_eq!(own_gid, uid);
+        Ok::<(),TestCaseError>(())
+});
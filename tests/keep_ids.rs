@@ -0,0 +1,37 @@
+use nix::fcntl::AtFlags;
+use nix::libc::{gid_t, uid_t};
+use nix::sys::stat::fstatat;
+use nix::unistd::{fchownat, Gid, Uid};
+use proptest::prelude::*;
+use subuidless_test::syscall;
+
+use crate::fchownat::{file_strategy, id_strategy};
+
+syscall!(
+    KeepIds {
+        #[proptest(strategy = "file_strategy()")]
+        path: String,
+        #[proptest(strategy = "id_strategy()")]
+        owner: uid_t,
+        #[proptest(strategy = "id_strategy()")]
+        group: gid_t,
+        keep_owner: bool
+    },
+    // Act
+    self {
+        fchownat(None, self.path.as_str(), Some(Uid::from_raw(self.owner)), Some(Gid::from_raw(self.group)), AtFlags::empty())?;
+        // `None` passes -1, which keeps the id set above
+        let (owner, group) = if self.keep_owner {
+            (None, Some(Gid::from_raw(self.owner)))
+        } else {
+            (Some(Uid::from_raw(self.group)), None)
+        };
+        fchownat(None, self.path.as_str(), owner, group, AtFlags::empty())?;
+        let stat = fstatat(None, self.path.as_str(), AtFlags::empty())?;
+        (stat.st_uid,stat.st_gid)
+    },
+    // Assert
+    test_keep_ids(keep_ids, (left,right): (uid_t, gid_t)) {
+        prop_assert_eq!(left, right);
+        Ok::<(),TestCaseError>(())
+});
@@ -0,0 +1,6 @@
+//! Compile-time checks of the `#[subuidless::syscall]` attribute
+#[test]
+fn syscall_attribute() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+}
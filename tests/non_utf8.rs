@@ -0,0 +1,47 @@
+use std::ffi::OsString;
+use std::os::unix::ffi::OsStringExt;
+use std::path::PathBuf;
+
+use nix::fcntl::{open, AtFlags, OFlag};
+use nix::libc::{gid_t, uid_t};
+use nix::sys::stat::{fstatat, Mode};
+use nix::unistd::{close, fchownat, Gid, Uid};
+use proptest::prelude::*;
+use proptest::strategy::Union;
+use subuidless_test::syscall;
+
+use crate::fchownat::id_strategy;
+
+/// Names with Latin-1 and other bytes that are not valid UTF-8
+pub fn file_strategy() -> impl Strategy<Value = Vec<u8>> {
+    Union::new(
+        include_bytes!("./files_non_utf8.txt")
+            .split(|&byte| byte == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| Just(line.to_vec())),
+    )
+}
+
+syscall!(
+    NonUtf8 {
+        #[proptest(strategy = "file_strategy()")]
+        path: Vec<u8>,
+        #[proptest(strategy = "id_strategy()")]
+        owner: uid_t,
+        #[proptest(strategy = "id_strategy()")]
+        group: gid_t
+    },
+    // Act
+    self {
+        let path = PathBuf::from(OsString::from_vec(self.path.clone()));
+        let file = open(&path, OFlag::O_CREAT | OFlag::O_WRONLY, Mode::S_IRUSR | Mode::S_IWUSR)?;
+        close(file)?;
+        fchownat(None, &path, Some(Uid::from_raw(self.owner)), Some(Gid::from_raw(self.group)), AtFlags::empty())?;
+        let stat = fstatat(None, &path, AtFlags::empty())?;
+        (stat.st_uid,stat.st_gid)
+    },
+    // Assert
+    test_non_utf8(non_utf8, (left,right): (uid_t, gid_t)) {
+        prop_assert_eq!(left, right);
+        Ok::<(),TestCaseError>(())
+});
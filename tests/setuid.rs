@@ -0,0 +1,114 @@
+use nix::errno::Errno;
+use nix::libc::{self, uid_t};
+use nix::sys::prctl::{get_keepcaps, set_keepcaps};
+use nix::unistd::{geteuid, getuid, setresuid, Uid};
+use proptest::prelude::*;
+use subuidless_test::syscall;
+
+use crate::fchownat::id_strategy;
+
+/// `_LINUX_CAPABILITY_VERSION_3`
+const VERSION_3: u32 = 0x2008_0522;
+
+/// The capability data `capget(2)` reports for the calling process:
+/// the effective, permitted and inheritable sets of the lower 32 capabilities, followed by the ones of the upper 32
+fn capget() -> Result<[u32; 6], Errno> {
+    // `struct __user_cap_header_struct` and two `struct __user_cap_data_struct`
+    let mut header = [VERSION_3, 0];
+    let mut data = [0_u32; 6];
+    #[allow(unsafe_code)]
+    // SAFETY:
+    // The buffers have the size of the header and the data of version 3
+    let result = unsafe { libc::syscall(libc::SYS_capget, header.as_mut_ptr(), data.as_mut_ptr()) };
+    Errno::result(result)?;
+    Ok(data)
+}
+
+/// `capset(2)` of the calling process with `data` in the layout of `capget`
+fn capset(data: [u32; 6]) -> Result<(), Errno> {
+    let header = [VERSION_3, 0];
+    #[allow(unsafe_code)]
+    // SAFETY:
+    // The buffers have the size of the header and the data of version 3, the kernel only reads them
+    let result = unsafe { libc::syscall(libc::SYS_capset, header.as_ptr(), data.as_ptr()) };
+    Errno::result(result)?;
+    Ok(())
+}
+
+/// The capability set at `index` of `data`: 0 is the effective, 1 the permitted and 2 the inheritable one
+fn capability_set(data: [u32; 6], index: usize) -> u64 {
+    let low = data.get(index).copied().unwrap_or_default();
+    let high = data
+        .get(index.saturating_add(3))
+        .copied()
+        .unwrap_or_default();
+    u64::from(low) | u64::from(high).checked_shl(32).unwrap_or_default()
+}
+
+/// The effective capabilities `capget(2)` reports for the calling process
+fn effective_caps() -> Result<u64, Errno> {
+    Ok(capability_set(capget()?, 0))
+}
+
+syscall!(
+    Setuid {
+        #[proptest(strategy = "id_strategy()")]
+        uid: uid_t
+    },
+    // Act
+    self {
+        let root = Uid::from_raw(0);
+        let uid = Uid::from_raw(self.uid);
+        // The saved uid stays root, so the process can switch back
+        setresuid(uid, uid, root)?;
+        let ids = (getuid().as_raw(), geteuid().as_raw());
+        let caps = effective_caps()?;
+        setresuid(root, root, root)?;
+        (self.uid, ids, caps, effective_caps()?)
+    },
+    // Assert
+    test_setuid(setuid, (uid, ids, caps, restored): (uid_t, (uid_t, uid_t), u64, u64)) {
+        prop_assert_eq!(ids, (uid, uid));
+        // Only root keeps its capabilities effective
+        prop_assert_eq!(caps == 0, uid != 0);
+        prop_assert_ne!(restored, 0);
+        Ok::<(),TestCaseError>(())
+});
+
+syscall!(
+    KeepCaps {
+        #[proptest(strategy = "id_strategy()")]
+        uid: uid_t
+    },
+    // Act
+    self {
+        let root = Uid::from_raw(0);
+        let uid = Uid::from_raw(self.uid);
+        // Like `capng_change_id` of libcap-ng: keep the capabilities, drop all root ids and raise the permitted ones again
+        set_keepcaps(true)?;
+        let kept = get_keepcaps()?;
+        setresuid(uid, uid, uid)?;
+        let dropped = capget()?;
+        let [_, permitted_low, inheritable_low, _, permitted_high, inheritable_high] = dropped;
+        capset([
+            permitted_low,
+            permitted_low,
+            inheritable_low,
+            permitted_high,
+            permitted_high,
+            inheritable_high,
+        ])?;
+        let effective = effective_caps()?;
+        setresuid(root, root, root)?;
+        set_keepcaps(false)?;
+        (self.uid, kept, capability_set(dropped, 0), capability_set(dropped, 1), effective)
+    },
+    // Assert
+    test_keep_caps(keep_caps, (uid, kept, effective, permitted, raised): (uid_t, bool, u64, u64, u64)) {
+        prop_assert!(kept);
+        prop_assert_ne!(permitted, 0);
+        // The effective capabilities are only dropped with the effective uid
+        prop_assert_eq!(effective == 0, uid != 0);
+        prop_assert_eq!(raised, permitted);
+        Ok::<(),TestCaseError>(())
+});
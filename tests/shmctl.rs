@@ -0,0 +1,71 @@
+use std::mem::zeroed;
+
+use nix::errno::Errno;
+use nix::libc::{
+    self, c_int, shmid_ds, uid_t, IPC_CREAT, IPC_PRIVATE, IPC_RMID, IPC_SET, IPC_STAT,
+};
+use nix::unistd::{setresuid, Uid};
+use proptest::prelude::*;
+use subuidless_test::syscall;
+
+use crate::fchownat::id_strategy;
+
+/// `shmctl(2)` of the segment `id` with `cmd` and `ds`
+fn shmctl(id: c_int, cmd: c_int, ds: &mut shmid_ds) -> Result<(), Errno> {
+    #[allow(unsafe_code)]
+    // SAFETY:
+    // `ds` is a valid `shmid_ds`, which the kernel reads or writes at most
+    let result = unsafe { libc::shmctl(id, cmd, ds) };
+    Errno::result(result)?;
+    Ok(())
+}
+
+/// The owner and creator of the segment `id`
+fn owner(id: c_int) -> Result<(uid_t, uid_t), Errno> {
+    #[allow(unsafe_code)]
+    // SAFETY:
+    // `shmid_ds` only contains integers
+    let mut ds: shmid_ds = unsafe { zeroed() };
+    shmctl(id, IPC_STAT, &mut ds)?;
+    Ok((ds.shm_perm.uid, ds.shm_perm.cuid))
+}
+
+syscall!(
+    Shmctl {
+        #[proptest(strategy = "id_strategy()")]
+        uid: uid_t,
+        #[proptest(strategy = "id_strategy()")]
+        new_uid: uid_t
+    },
+    // Act
+    self {
+        let root = Uid::from_raw(0);
+        // The saved uid stays root, so the process can switch back
+        setresuid(Uid::from_raw(self.uid), Uid::from_raw(self.uid), root)?;
+        #[allow(unsafe_code)]
+        // SAFETY:
+        // The syscall only takes integer arguments
+        let id = Errno::result(unsafe { libc::shmget(IPC_PRIVATE, 4096, IPC_CREAT | 0o600) })?;
+        let created = owner(id)?;
+        setresuid(root, root, root)?;
+
+        #[allow(unsafe_code)]
+        // SAFETY:
+        // See `owner`
+        let mut ds: shmid_ds = unsafe { zeroed() };
+        shmctl(id, IPC_STAT, &mut ds)?;
+        ds.shm_perm.uid = self.new_uid;
+        shmctl(id, IPC_SET, &mut ds)?;
+        let changed = owner(id)?;
+
+        shmctl(id, IPC_RMID, &mut ds)?;
+        (self.uid, self.new_uid, created, changed, owner(id).err())
+    },
+    // Assert
+    test_shmctl(shmctl, (uid, new_uid, created, changed, removed): (uid_t, uid_t, (uid_t, uid_t), (uid_t, uid_t), Option<Errno>)) {
+        prop_assert_eq!(created, (uid, uid));
+        // `IPC_SET` changes the owner, the creator stays the same
+        prop_assert_eq!(changed, (new_uid, uid));
+        prop_assert_eq!(removed, Some(Errno::EINVAL));
+        Ok::<(),TestCaseError>(())
+});
@@ -0,0 +1,11 @@
+#[subuidless::syscall]
+fn fstat(
+    notification: &subuidless::syscall::Notification,
+    #[fd]
+    #[out]
+    fd: &mut std::os::fd::RawFd,
+) -> Result<subuidless::syscall::Outcome, subuidless::Error> {
+    Ok(subuidless::syscall::Outcome::Continue)
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+#[subuidless::syscall]
+fn fstat(
+    notification: &subuidless::syscall::Notification,
+    fd: i32,
+    #[out] statbuf: nix::sys::stat::FileStat,
+) -> Result<subuidless::syscall::Outcome, subuidless::Error> {
+    Ok(subuidless::syscall::Outcome::Continue)
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+#[subuidless::syscall]
+fn syscall(
+    notification: &subuidless::syscall::Notification,
+    a: u64,
+    b: u64,
+    c: u64,
+    d: u64,
+    e: u64,
+    f: u64,
+    g: u64,
+) -> Result<subuidless::syscall::Outcome, subuidless::Error> {
+    Ok(subuidless::syscall::Outcome::Continue)
+}
+
+fn main() {}